@@ -1,31 +1,272 @@
 use {
-  prettytable::{format, Row as PrettyRow, Table as PrettyTable},
+  prettytable::{
+    format::{self, TableFormat},
+    Cell, Row as PrettyRow, Table as PrettyTable,
+  },
+  rand::{seq::index, Rng},
   std::{
-    any::Any, cell::RefCell, collections::BTreeMap, fmt, marker::PhantomData,
+    any::Any,
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    io,
+    marker::PhantomData,
     rc::Rc,
+    sync::Arc,
   },
   thiserror::Error,
 };
 
+mod sql;
+
 #[derive(Error, Debug)]
 pub enum Error {
-  #[error("Table '{0}' already exists")]
-  TableAlreadyExists(String),
+  #[error("Table '{0}' already exists with row type '{1}'")]
+  TableAlreadyExists(String, &'static str),
   #[error("Table '{0}' not found")]
   TableNotFound(String),
   #[error("Invalid row type for table '{0}'")]
   InvalidRowType(String),
+  #[error("Column '{0}' not found")]
+  ColumnNotFound(String),
+  #[error("Parse error: {0}")]
+  ParseError(String),
+  #[error("Unsupported: {0}")]
+  Unsupported(String),
+  #[error("Check failed for row inserted into table '{0}'")]
+  CheckFailed(String),
+  #[error("Expected {expected} columns, got {actual}")]
+  ColumnCountMismatch { expected: usize, actual: usize },
+  #[error("Null value in row {row}, column {column}")]
+  NullViolation { row: usize, column: usize },
+  #[error(
+    "Foreign key violation in table '{table}', column '{column}', row {row}"
+  )]
+  ForeignKeyViolation {
+    table: String,
+    column: String,
+    row: usize,
+  },
+}
+
+fn group_digits(value: &str) -> Option<String> {
+  let (sign, unsigned) = match value.strip_prefix('-') {
+    Some(rest) => ("-", rest),
+    None => ("", value),
+  };
+
+  let (integer, fraction) = match unsigned.split_once('.') {
+    Some((integer, fraction)) => (integer, Some(fraction)),
+    None => (unsigned, None),
+  };
+
+  let is_digits =
+    |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+  if !is_digits(integer) || !fraction.is_none_or(is_digits) {
+    return None;
+  }
+
+  let mut grouped = String::from(sign);
+
+  for (i, digit) in integer.chars().enumerate() {
+    if i > 0 && (integer.len() - i) % 3 == 0 {
+      grouped.push(',');
+    }
+
+    grouped.push(digit);
+  }
+
+  if let Some(fraction) = fraction {
+    grouped.push('.');
+    grouped.push_str(fraction);
+  }
+
+  Some(grouped)
+}
+
+pub type Records = (Vec<String>, Vec<Vec<String>>);
+
+pub fn render(headers: &[String], records: &[Vec<String>]) -> String {
+  let mut pretty_table = PrettyTable::new();
+
+  pretty_table.set_format(*format::consts::FORMAT_BOX_CHARS);
+  pretty_table.set_titles(PrettyRow::from(headers));
+
+  for record in records {
+    pretty_table.add_row(PrettyRow::from(record));
+  }
+
+  pretty_table.to_string()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellValue {
+  Int(i64),
+  Float(f64),
+  Text(String),
+  Null,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullsOrder {
+  First,
+  Last,
+}
+
+impl CompareOp {
+  fn matches(self, ordering: Ordering) -> bool {
+    match self {
+      CompareOp::Eq => ordering == Ordering::Equal,
+      CompareOp::Ne => ordering != Ordering::Equal,
+      CompareOp::Lt => ordering == Ordering::Less,
+      CompareOp::Le => ordering != Ordering::Greater,
+      CompareOp::Gt => ordering == Ordering::Greater,
+      CompareOp::Ge => ordering != Ordering::Less,
+    }
+  }
+}
+
+impl CellValue {
+  /// Compares two numbers or two text values, returning `None` for nulls and
+  /// for mismatched kinds such as text against a number.
+  pub fn compare(&self, other: &CellValue) -> Option<Ordering> {
+    match (self, other) {
+      (CellValue::Null, _) | (_, CellValue::Null) => None,
+      (CellValue::Text(_), CellValue::Text(_)) => Some(self.total_cmp(other)),
+      (CellValue::Text(_), _) | (_, CellValue::Text(_)) => None,
+      _ => Some(self.total_cmp(other)),
+    }
+  }
+
+  /// Orders nulls first, then numbers by value (mixing ints and floats), then
+  /// text lexicographically.
+  pub fn total_cmp(&self, other: &CellValue) -> Ordering {
+    fn rank(value: &CellValue) -> u8 {
+      match value {
+        CellValue::Null => 0,
+        CellValue::Int(_) | CellValue::Float(_) => 1,
+        CellValue::Text(_) => 2,
+      }
+    }
+
+    match (self, other) {
+      (CellValue::Int(a), CellValue::Int(b)) => a.cmp(b),
+      (CellValue::Int(a), CellValue::Float(b)) => (*a as f64).total_cmp(b),
+      (CellValue::Float(a), CellValue::Int(b)) => a.total_cmp(&(*b as f64)),
+      (CellValue::Float(a), CellValue::Float(b)) => a.total_cmp(b),
+      (CellValue::Text(a), CellValue::Text(b)) => a.cmp(b),
+      _ => rank(self).cmp(&rank(other)),
+    }
+  }
 }
 
 pub trait Row: fmt::Debug + Clone + Any {
   fn header() -> PrettyRow;
   fn to_pretty_row(&self) -> PrettyRow;
+
+  fn column_count() -> usize {
+    Self::header().len()
+  }
+
+  fn null_row() -> PrettyRow {
+    PrettyRow::from(vec![""; Self::column_count()])
+  }
+
+  fn values(&self) -> Vec<String> {
+    self
+      .to_pretty_row()
+      .iter()
+      .map(|cell| cell.get_content())
+      .collect()
+  }
+
+  fn from_values(_values: &[String]) -> Result<Self, Error> {
+    Err(Error::Unsupported(format!(
+      "row type '{}' cannot be built from values",
+      std::any::type_name::<Self>()
+    )))
+  }
+
+  fn cell(&self, col: usize) -> Option<CellValue> {
+    self.values().into_iter().nth(col).map(|value| {
+      if value.is_empty() {
+        CellValue::Null
+      } else {
+        CellValue::Text(value)
+      }
+    })
+  }
+}
+
+trait AnyIndex<T>: Send + Sync {
+  fn as_any(&self) -> &dyn Any;
+  fn clone_box(&self) -> Box<dyn AnyIndex<T>>;
+  fn insert(&mut self, row: &T, position: usize);
+}
+
+impl<T> Clone for Box<dyn AnyIndex<T>> {
+  fn clone(&self) -> Self {
+    self.clone_box()
+  }
+}
+
+impl<T> fmt::Debug for dyn AnyIndex<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Index")
+  }
+}
+
+struct Index<T, K> {
+  key: Arc<dyn Fn(&T) -> K + Send + Sync>,
+  positions: BTreeMap<K, Vec<usize>>,
+}
+
+impl<T: 'static, K: Ord + Clone + Send + Sync + 'static> AnyIndex<T>
+  for Index<T, K>
+{
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn clone_box(&self) -> Box<dyn AnyIndex<T>> {
+    Box::new(Index {
+      key: self.key.clone(),
+      positions: self.positions.clone(),
+    })
+  }
+
+  fn insert(&mut self, row: &T, position: usize) {
+    self
+      .positions
+      .entry((self.key)(row))
+      .or_default()
+      .push(position);
+  }
 }
 
 #[derive(Debug, Clone)]
 pub struct Table<T: Row> {
   name: String,
+  format: TableFormat,
+  number_grouping: bool,
+  pad_short_rows: bool,
+  runtime_headers: Option<Vec<String>>,
   rows: Vec<T>,
+  indexes: BTreeMap<String, Box<dyn AnyIndex<T>>>,
+  join_sources: Option<(String, String)>,
   phantom: PhantomData<fn() -> T>,
 }
 
@@ -33,263 +274,4355 @@ impl<T: Row> Table<T> {
   fn new(name: String) -> Self {
     Self {
       name,
+      format: *format::consts::FORMAT_BOX_CHARS,
+      number_grouping: false,
+      pad_short_rows: false,
+      runtime_headers: None,
       rows: Vec::new(),
+      indexes: BTreeMap::new(),
+      join_sources: None,
       phantom: PhantomData,
     }
   }
 
-  pub fn insert(&mut self, row: T) {
-    self.rows.push(row);
+  fn derive(&self, rows: Vec<T>) -> Self {
+    Self {
+      name: self.name.clone(),
+      format: self.format,
+      number_grouping: self.number_grouping,
+      pad_short_rows: self.pad_short_rows,
+      runtime_headers: self.runtime_headers.clone(),
+      rows,
+      indexes: BTreeMap::new(),
+      join_sources: self.join_sources.clone(),
+      phantom: PhantomData,
+    }
   }
 
-  pub fn insert_many(&mut self, rows: &[T]) {
-    self.rows.extend(rows.iter().cloned());
+  pub fn from_rows(name: &str, rows: Vec<T>) -> Self {
+    let mut table = Self::new(name.to_string());
+    table.rows = rows;
+    table
   }
-}
 
-impl<T: Row> fmt::Display for Table<T> {
-  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    let mut pretty_table = PrettyTable::new();
+  pub fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+  }
 
-    pretty_table.set_format(*format::consts::FORMAT_BOX_CHARS);
-    pretty_table.set_titles(T::header());
+  pub fn named(mut self, name: &str) -> Self {
+    self.name = name.to_string();
+    self
+  }
 
-    for row in &self.rows {
-      pretty_table.add_row(row.to_pretty_row());
-    }
+  pub fn with_format(mut self, format: TableFormat) -> Self {
+    self.format = format;
+    self
+  }
 
-    write!(f, "{}", pretty_table)
+  pub fn compact_clone(&self) -> Table<T> {
+    let mut table = self.clone();
+    table.rows.shrink_to_fit();
+    table
   }
-}
 
-#[derive(Debug, Clone)]
-pub struct JoinedRow<T: Row, U: Row> {
-  left: T,
-  right: U,
-}
+  pub fn or_default_row(mut self, row: T) -> Self {
+    if self.rows.is_empty() {
+      self.insert(row);
+    }
 
-impl<T: Row, U: Row> Row for JoinedRow<T, U> {
-  fn header() -> PrettyRow {
-    let mut header = T::header();
-    header.extend(U::header().iter().cloned());
-    header
+    self
   }
 
-  fn to_pretty_row(&self) -> PrettyRow {
-    let mut row = self.left.to_pretty_row();
-    row.extend(self.right.to_pretty_row().iter().cloned());
-    row
+  pub fn set_pad_short_rows(&mut self, pad: bool) {
+    self.pad_short_rows = pad;
   }
-}
 
-#[derive(Default)]
-pub struct Database {
-  tables: BTreeMap<String, Rc<RefCell<dyn Any>>>,
-}
+  pub fn set_number_grouping(&mut self, enabled: bool) {
+    self.number_grouping = enabled;
+  }
 
-impl Database {
-  pub fn new() -> Self {
-    Self {
-      tables: BTreeMap::new(),
+  pub fn insert(&mut self, row: T) {
+    for index in self.indexes.values_mut() {
+      index.insert(&row, self.rows.len());
     }
+
+    self.rows.push(row);
   }
 
-  pub fn create_table<T: Row + 'static>(
+  pub fn insert_if(
     &mut self,
-    name: &str,
-  ) -> Result<Rc<RefCell<Table<T>>>, Error> {
-    if self.tables.contains_key(name) {
-      return Err(Error::TableAlreadyExists(name.to_string()));
+    row: T,
+    valid: impl Fn(&T) -> bool,
+  ) -> Result<(), Error> {
+    if !valid(&row) {
+      return Err(Error::CheckFailed(self.name.clone()));
     }
 
-    let table = Rc::new(RefCell::new(Table::<T>::new(name.to_string())));
+    self.insert(row);
 
-    self.tables.insert(name.to_string(), table.clone());
+    Ok(())
+  }
 
-    Ok(table)
+  pub fn insert_many(&mut self, rows: &[T]) {
+    for row in rows {
+      self.insert(row.clone());
+    }
   }
 
-  pub fn cross_join<T: Row + 'static, U: Row + 'static>(
-    &self,
-    table_a: &Table<T>,
-    table_b: &Table<U>,
-  ) -> Table<JoinedRow<T, U>> {
-    let mut joined_table =
-      Table::new(format!("{}_cross_{}", table_a.name, table_b.name));
+  pub fn insert_dedup<K: Eq + Hash>(
+    &mut self,
+    rows: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> K,
+  ) -> usize {
+    let mut keys = self.rows.iter().map(&key).collect::<HashSet<_>>();
 
-    for left_row in &table_a.rows {
-      for right_row in &table_b.rows {
-        joined_table.insert(JoinedRow {
-          left: left_row.clone(),
-          right: right_row.clone(),
-        });
+    let mut inserted = 0;
+
+    for row in rows {
+      if keys.insert(key(&row)) {
+        self.insert(row);
+        inserted += 1;
       }
     }
 
-    joined_table
+    inserted
   }
 
-  #[cfg(test)]
-  pub fn from<T: Row + 'static>(
-    &self,
-    table_name: &str,
-  ) -> Result<Rc<RefCell<Table<T>>>, Error> {
-    match self.tables.get(table_name) {
-      Some(table) => table
-        .borrow()
-        .downcast_ref::<Table<T>>()
-        .map(|t| Rc::new(RefCell::new(t.clone())))
-        .ok_or_else(|| Error::InvalidRowType(table_name.to_string())),
-      None => Err(Error::TableNotFound(table_name.to_string())),
+  /// Builds a non-unique index over `key` named `name`, kept up to date on
+  /// insert. Creating another index with the same name replaces it.
+  pub fn create_index<K: Ord + Clone + Send + Sync + 'static>(
+    &mut self,
+    name: &str,
+    key: impl Fn(&T) -> K + Send + Sync + 'static,
+  ) {
+    let mut index = Index {
+      key: Arc::new(key),
+      positions: BTreeMap::new(),
+    };
+
+    for (position, row) in self.rows.iter().enumerate() {
+      index.insert(row, position);
     }
-  }
-}
 
-#[cfg(test)]
-mod tests {
-  use {super::*, prettytable::row};
+    self.indexes.insert(name.to_string(), Box::new(index));
+  }
 
-  #[derive(Debug, Clone, PartialEq)]
-  struct Book {
-    id: u32,
-    name: String,
-    author_id: u32,
+  /// Returns the positions of every row whose key in the index `name` equals
+  /// `key`, or an empty slice if there is no such index keyed by `K`.
+  pub fn indices_by_key<K: Ord + Clone + Send + Sync + 'static>(
+    &self,
+    name: &str,
+    key: &K,
+  ) -> &[usize] {
+    self
+      .indexes
+      .get(name)
+      .and_then(|index| index.as_any().downcast_ref::<Index<T, K>>())
+      .and_then(|index| index.positions.get(key))
+      .map_or(&[], Vec::as_slice)
   }
 
-  impl Row for Book {
-    fn header() -> PrettyRow {
-      row!["ID", "Name", "Author ID"]
+  fn headers(&self) -> Vec<String> {
+    match &self.runtime_headers {
+      Some(headers) => headers.clone(),
+      None => T::header().iter().map(|cell| cell.get_content()).collect(),
     }
+  }
 
-    fn to_pretty_row(&self) -> PrettyRow {
-      row![self.id, self.name, self.author_id]
+  pub fn set_runtime_headers(
+    &mut self,
+    headers: Vec<String>,
+  ) -> Result<(), Error> {
+    if headers.len() != T::column_count() {
+      return Err(Error::ColumnCountMismatch {
+        expected: T::column_count(),
+        actual: headers.len(),
+      });
     }
-  }
 
-  #[derive(Debug, Clone, PartialEq)]
-  struct Author {
-    id: u32,
-    name: String,
+    self.runtime_headers = Some(headers);
+
+    Ok(())
   }
 
-  impl Row for Author {
-    fn header() -> PrettyRow {
-      row!["ID", "Name"]
-    }
+  /// Loads every record of `input`, whose first line is a header that becomes
+  /// the table's runtime headers. Nothing is inserted unless every record
+  /// parses.
+  pub fn load_csv(
+    &mut self,
+    input: &str,
+    parse: impl Fn(&[String]) -> Result<T, Error>,
+  ) -> Result<(), Error> {
+    let mut reader = csv::ReaderBuilder::new()
+      .flexible(true)
+      .from_reader(input.as_bytes());
 
-    fn to_pretty_row(&self) -> PrettyRow {
-      row![self.id, self.name]
+    let headers = reader
+      .headers()
+      .map_err(|error| Error::ParseError(error.to_string()))?
+      .iter()
+      .map(str::to_string)
+      .collect::<Vec<_>>();
+
+    if headers.len() != T::column_count() {
+      return Err(Error::ColumnCountMismatch {
+        expected: T::column_count(),
+        actual: headers.len(),
+      });
     }
-  }
 
-  #[test]
-  fn create_table() {
-    let mut db = Database::new();
+    let rows = reader
+      .records()
+      .map(|record| {
+        let record =
+          record.map_err(|error| Error::ParseError(error.to_string()))?;
 
-    assert!(db.create_table::<Book>("books").is_ok());
+        let mut values = record.iter().map(str::to_string).collect::<Vec<_>>();
 
-    let err = db.create_table::<Book>("books");
+        if self.pad_short_rows && values.len() < T::column_count() {
+          values.resize(T::column_count(), String::new());
+        }
 
-    assert!(matches!(err, Err(Error::TableAlreadyExists(_))));
-  }
+        if values.len() != T::column_count() {
+          return Err(Error::ColumnCountMismatch {
+            expected: T::column_count(),
+            actual: values.len(),
+          });
+        }
 
-  #[test]
-  fn insert_single() {
-    let mut db = Database::new();
+        parse(&values)
+      })
+      .collect::<Result<Vec<_>, _>>()?;
 
-    let books = db.create_table::<Book>("books").unwrap();
+    self.set_runtime_headers(headers)?;
 
-    books.borrow_mut().insert(Book {
-      id: 1,
-      name: "Test Book".to_string(),
-      author_id: 1,
-    });
+    for row in rows {
+      self.insert(row);
+    }
 
-    let table = db.from::<Book>("books").unwrap();
+    Ok(())
+  }
 
-    assert_eq!(table.borrow().rows.len(), 1);
+  fn records(&self) -> Vec<Vec<String>> {
+    self.rows.iter().map(Row::values).collect()
+  }
 
-    assert_eq!(table.borrow().rows[0].name, "Test Book");
+  pub fn column_index(&self, title: &str) -> Option<usize> {
+    self.headers().iter().position(|header| header == title)
   }
 
-  #[test]
-  fn insert_many() {
-    let mut db = Database::new();
+  pub fn sort_by_column_name(
+    &self,
+    title: &str,
+    ascending: bool,
+  ) -> Result<Table<T>, Error> {
+    let index = self
+      .column_index(title)
+      .ok_or_else(|| Error::ColumnNotFound(title.to_string()))?;
 
-    let books = db.create_table::<Book>("books").unwrap();
+    let mut keyed = self
+      .rows
+      .iter()
+      .map(|row| {
+        let value = row.values().get(index).cloned().unwrap_or_default();
+        (value, row.clone())
+      })
+      .collect::<Vec<_>>();
 
-    books.borrow_mut().insert_many(&[
-      Book {
-        id: 1,
-        name: "Book 1".to_string(),
-        author_id: 1,
-      },
-      Book {
-        id: 2,
-        name: "Book 2".to_string(),
-        author_id: 2,
-      },
-    ]);
+    keyed.sort_by(|(a, _), (b, _)| if ascending { a.cmp(b) } else { b.cmp(a) });
 
-    let table = db.from::<Book>("books").unwrap();
+    Ok(self.derive(keyed.into_iter().map(|(_, row)| row).collect()))
+  }
 
-    assert_eq!(table.borrow().rows.len(), 2);
+  pub fn select_columns(&self, titles: &[&str]) -> Result<Records, Error> {
+    let indices = titles
+      .iter()
+      .map(|title| {
+        self
+          .column_index(title)
+          .ok_or_else(|| Error::ColumnNotFound(title.to_string()))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
 
-    assert_eq!(table.borrow().rows[0].name, "Book 1");
+    let records = self
+      .rows
+      .iter()
+      .map(|row| {
+        let values = row.values();
+        indices
+          .iter()
+          .map(|&i| values.get(i).cloned().unwrap_or_default())
+          .collect()
+      })
+      .collect();
 
-    assert_eq!(table.borrow().rows[1].name, "Book 2");
+    Ok((
+      titles.iter().map(|title| title.to_string()).collect(),
+      records,
+    ))
   }
 
-  #[test]
-  fn cross_join() {
-    let mut db = Database::new();
+  pub fn reorder_columns_to(&self, titles: &[&str]) -> Result<Records, Error> {
+    self.select_columns(titles)
+  }
 
-    let books = db.create_table::<Book>("books").unwrap();
+  pub fn with_computed_column(
+    &self,
+    title: &str,
+    f: impl Fn(&T) -> String,
+  ) -> Records {
+    let mut headers = self.headers();
 
-    books.borrow_mut().insert_many(&[
-      Book {
-        id: 1,
-        name: "1984".to_string(),
-        author_id: 1,
-      },
-      Book {
-        id: 2,
-        name: "Animal Farm".to_string(),
-        author_id: 1,
-      },
-    ]);
+    headers.push(title.to_string());
 
-    let authors = db.create_table::<Author>("authors").unwrap();
+    let records = self
+      .rows
+      .iter()
+      .map(|row| {
+        let mut values = row.values();
+        values.resize(headers.len() - 1, String::new());
+        values.push(f(row));
+        values
+      })
+      .collect();
 
-    authors.borrow_mut().insert_many(&[
-      Author {
-        id: 1,
-        name: "George Orwell".to_string(),
-      },
-      Author {
-        id: 2,
-        name: "Aldous Huxley".to_string(),
-      },
-    ]);
+    (headers, records)
+  }
 
-    let joined_table = db.cross_join(&books.borrow(), &authors.borrow());
+  pub fn filter(&self, predicate: impl Fn(&T) -> bool) -> Table<T> {
+    self.derive(
+      self
+        .rows
+        .iter()
+        .filter(|row| predicate(row))
+        .cloned()
+        .collect(),
+    )
+  }
 
-    assert_eq!(joined_table.rows.len(), 4);
+  #[cfg(feature = "rayon")]
+  pub fn par_filter(&self, predicate: impl Fn(&T) -> bool + Sync) -> Table<T>
+  where
+    T: Send + Sync,
+  {
+    use rayon::prelude::*;
+
+    self.derive(
+      self
+        .rows
+        .par_iter()
+        .filter(|row| predicate(row))
+        .cloned()
+        .collect(),
+    )
+  }
+
+  pub fn diff(&self, other: &Table<T>) -> TableDiff {
+    fn unmatched(
+      records: Vec<Vec<String>>,
+      against: &[Vec<String>],
+    ) -> Vec<Vec<String>> {
+      let mut remaining = HashMap::new();
+
+      for record in against {
+        *remaining.entry(record).or_insert(0usize) += 1;
+      }
+
+      records
+        .into_iter()
+        .filter(|record| match remaining.get_mut(record) {
+          Some(count) if *count > 0 => {
+            *count -= 1;
+            false
+          }
+          _ => true,
+        })
+        .collect()
+    }
+
+    let (ours, theirs) = (self.records(), other.records());
+
+    TableDiff {
+      only_in_self: unmatched(ours.clone(), &theirs),
+      only_in_other: unmatched(theirs, &ours),
+    }
+  }
+
+  /// Renders the header and every row with each column left-aligned and padded
+  /// to its width. Cells longer than their width are truncated without an
+  /// ellipsis, so every line has exactly `widths.iter().sum()` characters.
+  pub fn to_fixed_width(&self, widths: &[usize]) -> Result<String, Error> {
+    if widths.len() != T::column_count() {
+      return Err(Error::ColumnCountMismatch {
+        expected: T::column_count(),
+        actual: widths.len(),
+      });
+    }
+
+    let mut output = String::new();
+
+    for values in std::iter::once(self.headers()).chain(self.records()) {
+      for (i, &width) in widths.iter().enumerate() {
+        let value = values.get(i).map_or("", String::as_str);
+        let cell = value.chars().take(width).collect::<String>();
+        output.push_str(&format!("{cell:<width$}"));
+      }
+
+      output.push('\n');
+    }
+
+    Ok(output)
+  }
+
+  pub fn merge_sorted<K: Ord>(
+    &self,
+    other: &Table<T>,
+    key: impl Fn(&T) -> K,
+  ) -> Table<T> {
+    let mut merged =
+      self.derive(Vec::with_capacity(self.rows.len() + other.rows.len()));
+
+    let (mut left, mut right) =
+      (self.rows.iter().peekable(), other.rows.iter().peekable());
+
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+      if key(b) < key(a) {
+        merged.rows.extend(right.next().cloned());
+      } else {
+        merged.rows.extend(left.next().cloned());
+      }
+    }
+
+    merged.rows.extend(left.cloned());
+    merged.rows.extend(right.cloned());
+
+    merged
+  }
+
+  pub fn to_csv(&self) -> io::Result<String> {
+    let mut output = Vec::new();
+
+    self.write_csv(&mut output)?;
+
+    String::from_utf8(output)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+  }
+
+  pub fn write_csv<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(w);
+
+    writer.write_record(self.headers())?;
+
+    for row in &self.rows {
+      let mut values = row.values();
+      values.resize(T::column_count(), String::new());
+      writer.write_record(values)?;
+    }
+
+    writer.flush()
+  }
+
+  pub fn sort_dedup_by_key<K: Ord + Clone>(
+    &self,
+    key: impl Fn(&T) -> K,
+  ) -> Table<T> {
+    let mut keyed = self
+      .rows
+      .iter()
+      .map(|row| (key(row), row.clone()))
+      .collect::<Vec<_>>();
+
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    keyed.dedup_by(|(a, _), (b, _)| a == b);
+
+    self.derive(keyed.into_iter().map(|(_, row)| row).collect())
+  }
+
+  pub fn order_by_cell(&self, col: usize, ascending: bool) -> Table<T> {
+    let nulls = if ascending {
+      NullsOrder::First
+    } else {
+      NullsOrder::Last
+    };
+
+    self.order_by_cell_with(col, ascending, nulls)
+  }
+
+  /// Sorts by the typed cell at `col`, placing null and missing cells at the
+  /// end chosen by `nulls` regardless of the sort direction.
+  pub fn order_by_cell_with(
+    &self,
+    col: usize,
+    ascending: bool,
+    nulls: NullsOrder,
+  ) -> Table<T> {
+    let mut keyed = self
+      .rows
+      .iter()
+      .map(|row| (row.cell(col).unwrap_or(CellValue::Null), row.clone()))
+      .collect::<Vec<_>>();
+
+    keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+      (CellValue::Null, CellValue::Null) => Ordering::Equal,
+      (CellValue::Null, _) => match nulls {
+        NullsOrder::First => Ordering::Less,
+        NullsOrder::Last => Ordering::Greater,
+      },
+      (_, CellValue::Null) => match nulls {
+        NullsOrder::First => Ordering::Greater,
+        NullsOrder::Last => Ordering::Less,
+      },
+      _ => {
+        let ordering = a.total_cmp(b);
+
+        if ascending {
+          ordering
+        } else {
+          ordering.reverse()
+        }
+      }
+    });
+
+    self.derive(keyed.into_iter().map(|(_, row)| row).collect())
+  }
+
+  /// Keeps rows whose cell at `col` compares to `value` as `op` requires.
+  /// Nulls, missing cells and mismatched kinds never match, even for
+  /// `CompareOp::Ne`.
+  pub fn filter_cell(
+    &self,
+    col: usize,
+    op: CompareOp,
+    value: CellValue,
+  ) -> Table<T> {
+    self.filter(|row| {
+      row
+        .cell(col)
+        .and_then(|cell| cell.compare(&value))
+        .is_some_and(|ordering| op.matches(ordering))
+    })
+  }
+
+  /// Samples `fraction` of each group formed by `key`, rounded to the nearest
+  /// row, keeping the sampled rows in their original order.
+  pub fn stratified_sample<K: Ord + Clone>(
+    &self,
+    key: impl Fn(&T) -> K,
+    fraction: f64,
+    rng: &mut impl Rng,
+  ) -> Table<T> {
+    let fraction = fraction.clamp(0.0, 1.0);
+
+    let mut groups = BTreeMap::<K, Vec<usize>>::new();
+
+    for (position, row) in self.rows.iter().enumerate() {
+      groups.entry(key(row)).or_default().push(position);
+    }
+
+    let mut sampled = groups
+      .into_values()
+      .flat_map(|positions| {
+        let amount = (positions.len() as f64 * fraction).round() as usize;
+
+        index::sample(rng, positions.len(), amount)
+          .into_iter()
+          .map(|i| positions[i])
+          .collect::<Vec<_>>()
+      })
+      .collect::<Vec<_>>();
+
+    sampled.sort_unstable();
+
+    self.derive(sampled.into_iter().map(|i| self.rows[i].clone()).collect())
+  }
+
+  /// Maps each row into bucket `floor(value / bucket_size)`, rounding toward
+  /// negative infinity so that, with a bucket size of 10, values -10 through
+  /// -1 land in bucket -1. Panics if `bucket_size` is zero.
+  pub fn bucket_by<F: Fn(&T) -> i64>(
+    &self,
+    bucket_size: i64,
+    value: F,
+  ) -> BTreeMap<i64, Vec<usize>> {
+    assert!(bucket_size != 0, "bucket size must be non-zero");
+
+    let mut buckets = BTreeMap::<i64, Vec<usize>>::new();
+
+    for (position, row) in self.rows.iter().enumerate() {
+      let value = value(row);
+
+      let mut bucket = value / bucket_size;
+
+      if value % bucket_size != 0 && (value < 0) != (bucket_size < 0) {
+        bucket -= 1;
+      }
+
+      buckets.entry(bucket).or_default().push(position);
+    }
+
+    buckets
+  }
+
+  /// Fails on the first empty cell in any of `required_cols`, scanning row by
+  /// row. Columns past the end of a row count as empty.
+  pub fn assert_no_nulls(&self, required_cols: &[usize]) -> Result<(), Error> {
+    for (row, values) in self.records().into_iter().enumerate() {
+      for &column in required_cols {
+        if values.get(column).is_none_or(String::is_empty) {
+          return Err(Error::NullViolation { row, column });
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Keeps, for each key, the row with the greatest `value`, preferring the
+  /// first-seen row on ties. Survivors stay in their original order.
+  pub fn keep_max_by<K: Eq + Hash, V: Ord>(
+    &self,
+    key: impl Fn(&T) -> K,
+    value: impl Fn(&T) -> V,
+  ) -> Table<T> {
+    let mut best = HashMap::<K, (V, usize)>::new();
+
+    for (position, row) in self.rows.iter().enumerate() {
+      let candidate = value(row);
+
+      match best.entry(key(row)) {
+        Entry::Occupied(mut entry) => {
+          if candidate > entry.get().0 {
+            entry.insert((candidate, position));
+          }
+        }
+        Entry::Vacant(entry) => {
+          entry.insert((candidate, position));
+        }
+      }
+    }
+
+    let mut survivors = best
+      .into_values()
+      .map(|(_, position)| position)
+      .collect::<Vec<_>>();
+
+    survivors.sort_unstable();
+
+    self.derive(
+      survivors
+        .into_iter()
+        .map(|i| self.rows[i].clone())
+        .collect(),
+    )
+  }
+
+  pub fn find_duplicates<K: Eq + Hash + Clone>(
+    &self,
+    key: impl Fn(&T) -> K,
+  ) -> Table<T> {
+    let mut counts = HashMap::new();
+
+    for row in &self.rows {
+      *counts.entry(key(row)).or_insert(0usize) += 1;
+    }
+
+    self.filter(|row| counts[&key(row)] > 1)
+  }
+
+  pub fn aggregate_all<R: Row>(&self, agg: impl Fn(&[T]) -> R) -> Table<R> {
+    Table::from_rows(&self.name, vec![agg(&self.rows)])
+  }
+
+  pub fn where_in<K: Eq + Hash>(
+    &self,
+    key: impl Fn(&T) -> K,
+    values: &[K],
+  ) -> Table<T> {
+    let values = values.iter().collect::<HashSet<_>>();
+
+    self.filter(|row| values.contains(&key(row)))
+  }
+
+  pub fn where_not_in<K: Eq + Hash>(
+    &self,
+    key: impl Fn(&T) -> K,
+    values: &[K],
+  ) -> Table<T> {
+    let values = values.iter().collect::<HashSet<_>>();
+
+    self.filter(|row| !values.contains(&key(row)))
+  }
+
+  pub fn group_by<K: Ord + Clone>(
+    &self,
+    key: impl Fn(&T) -> K,
+  ) -> BTreeMap<K, Vec<&T>> {
+    let mut groups = BTreeMap::<K, Vec<&T>>::new();
+
+    for row in &self.rows {
+      groups.entry(key(row)).or_default().push(row);
+    }
+
+    groups
+  }
+
+  pub fn group_map<K: Ord + Clone, R>(
+    &self,
+    key: impl Fn(&T) -> K,
+    f: impl Fn(K, Vec<&T>) -> R,
+  ) -> Vec<R> {
+    self
+      .group_by(key)
+      .into_iter()
+      .map(|(key, rows)| f(key, rows))
+      .collect()
+  }
+
+  fn pretty_table(&self, format: TableFormat) -> PrettyTable {
+    let mut pretty_table = PrettyTable::new();
+
+    pretty_table.set_format(format);
+
+    match &self.runtime_headers {
+      Some(headers) => pretty_table.set_titles(PrettyRow::from(headers)),
+      None => pretty_table.set_titles(T::header()),
+    }
+
+    for row in &self.rows {
+      let pretty_row = row.to_pretty_row();
+
+      if self.number_grouping {
+        pretty_table.add_row(PrettyRow::new(
+          pretty_row
+            .iter()
+            .map(|cell| match group_digits(&cell.get_content()) {
+              Some(grouped) => Cell::new(&grouped),
+              None => cell.clone(),
+            })
+            .collect(),
+        ));
+      } else {
+        pretty_table.add_row(pretty_row);
+      }
+    }
+
+    pretty_table
+  }
+
+  pub fn to_ascii(&self) -> String {
+    self
+      .pretty_table(*format::consts::FORMAT_DEFAULT)
+      .to_string()
+  }
+
+  /// Keeps rows whose value is at least the value ranked at the top `pct`
+  /// percent boundary, rounding the row count up, so ties at the boundary are
+  /// all retained. Rows stay in their original order.
+  pub fn top_percentile<F: Fn(&T) -> f64>(
+    &self,
+    value: F,
+    pct: f64,
+  ) -> Table<T> {
+    let pct = pct.clamp(0.0, 100.0);
+
+    let mut values = self.rows.iter().map(&value).collect::<Vec<_>>();
+
+    let keep = (values.len() as f64 * pct / 100.0).ceil() as usize;
+
+    if keep == 0 {
+      return self.derive(Vec::new());
+    }
+
+    values.sort_by(|a, b| b.total_cmp(a));
+
+    let threshold = values[keep - 1];
+
+    self.filter(|row| value(row) >= threshold)
+  }
+
+  pub fn interleave(&self, other: &Table<T>) -> Table<T> {
+    let mut rows = Vec::with_capacity(self.rows.len() + other.rows.len());
+
+    let (mut left, mut right) = (self.rows.iter(), other.rows.iter());
+
+    loop {
+      match (left.next(), right.next()) {
+        (None, None) => break,
+        (a, b) => rows.extend(a.into_iter().chain(b).cloned()),
+      }
+    }
+
+    self.derive(rows)
+  }
+
+  /// Builds a map from `key` to row. When several rows share a key, the last
+  /// one wins.
+  pub fn to_map<K: Ord + Clone>(
+    &self,
+    key: impl Fn(&T) -> K,
+  ) -> BTreeMap<K, T> {
+    self
+      .rows
+      .iter()
+      .map(|row| (key(row), row.clone()))
+      .collect()
+  }
+
+  pub fn to_multi_map<K: Ord + Clone>(
+    &self,
+    key: impl Fn(&T) -> K,
+  ) -> BTreeMap<K, Vec<T>> {
+    self
+      .group_by(key)
+      .into_iter()
+      .map(|(key, rows)| (key, rows.into_iter().cloned().collect()))
+      .collect()
+  }
+
+  /// Returns the number of rows a cross join with a table of `other_len` rows
+  /// would produce, saturating at `usize::MAX`.
+  pub fn cross_size(&self, other_len: usize) -> usize {
+    self.rows.len().saturating_mul(other_len)
+  }
+
+  /// Returns `(distinct_keys, max_group_size, mean_group_size)` for the groups
+  /// formed by `key`, all zero for an empty table.
+  pub fn key_skew<K: Eq + Hash>(
+    &self,
+    key: impl Fn(&T) -> K,
+  ) -> (usize, usize, f64) {
+    let mut groups = HashMap::new();
+
+    for row in &self.rows {
+      *groups.entry(key(row)).or_insert(0usize) += 1;
+    }
+
+    if groups.is_empty() {
+      return (0, 0, 0.0);
+    }
+
+    let max = groups.values().copied().max().unwrap_or_default();
+
+    (
+      groups.len(),
+      max,
+      self.rows.len() as f64 / groups.len() as f64,
+    )
+  }
+}
+
+impl<T: Row> FromIterator<T> for Table<T> {
+  fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+    Table::from_rows("", iter.into_iter().collect())
+  }
+}
+
+impl<T: Row> IntoIterator for Table<T> {
+  type Item = T;
+  type IntoIter = std::vec::IntoIter<T>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.rows.into_iter()
+  }
+}
+
+impl<T: Row> fmt::Display for Table<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.pretty_table(self.format))
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinedRow<T: Row, U: Row> {
+  left: T,
+  right: U,
+}
+
+impl<T: Row, U: Row> Row for JoinedRow<T, U> {
+  fn header() -> PrettyRow {
+    let mut header = T::header();
+    header.extend(U::header().iter().cloned());
+    header
+  }
+
+  fn to_pretty_row(&self) -> PrettyRow {
+    let mut row = self.left.to_pretty_row();
+    row.extend(self.right.to_pretty_row().iter().cloned());
+    row
+  }
+}
+
+impl<T: Row, U: Row> Table<JoinedRow<T, U>> {
+  /// Prefixes each header with the name of the table its column came from in
+  /// the join that produced this table.
+  pub fn with_qualified_headers(mut self) -> Self {
+    let Some((left, right)) = &self.join_sources else {
+      return self;
+    };
+
+    let qualify = |table: &str, header: PrettyRow| {
+      header
+        .iter()
+        .map(|cell| format!("{table}.{}", cell.get_content()))
+        .collect::<Vec<_>>()
+    };
+
+    let mut headers = qualify(left, T::header());
+
+    headers.extend(qualify(right, U::header()));
+
+    self.runtime_headers = Some(headers);
+
+    self
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct OuterJoinedRow<T: Row, U: Row> {
+  left: T,
+  right: Option<U>,
+}
+
+impl<T: Row, U: Row> Row for OuterJoinedRow<T, U> {
+  fn header() -> PrettyRow {
+    let mut header = T::header();
+    header.extend(U::header().iter().cloned());
+    header
+  }
+
+  fn to_pretty_row(&self) -> PrettyRow {
+    let mut row = self.left.to_pretty_row();
+
+    let right = self
+      .right
+      .as_ref()
+      .map_or_else(U::null_row, Row::to_pretty_row);
+
+    row.extend(right.iter().cloned());
+    row
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableDiff {
+  pub only_in_self: Vec<Vec<String>>,
+  pub only_in_other: Vec<Vec<String>>,
+}
+
+impl TableDiff {
+  pub fn is_empty(&self) -> bool {
+    self.only_in_self.is_empty() && self.only_in_other.is_empty()
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatabaseDiff {
+  pub only_in_self: Vec<String>,
+  pub only_in_other: Vec<String>,
+  pub changed: BTreeMap<String, TableDiff>,
+  pub type_mismatches: Vec<String>,
+}
+
+impl DatabaseDiff {
+  pub fn is_empty(&self) -> bool {
+    self.only_in_self.is_empty()
+      && self.only_in_other.is_empty()
+      && self.changed.is_empty()
+      && self.type_mismatches.is_empty()
+  }
+}
+
+trait AnyTable {
+  fn as_any(&self) -> &dyn Any;
+  fn clone_box(&self) -> Box<dyn AnyTable>;
+  fn cell(&self, row: usize, col: usize) -> Option<CellValue>;
+  fn diff(&self, other: &dyn AnyTable) -> Option<TableDiff>;
+  fn headers(&self) -> Vec<String>;
+  fn insert_values(&mut self, values: &[String]) -> Result<(), Error>;
+  fn into_shared(self: Box<Self>) -> Rc<RefCell<dyn AnyTable>>;
+  fn memory_estimate(&self) -> usize;
+  fn records(&self) -> Vec<Vec<String>>;
+  fn restore(&mut self, snapshot: &dyn AnyTable) -> bool;
+  fn row_count(&self) -> usize;
+  fn set_name(&mut self, name: &str);
+  fn type_name(&self) -> &'static str;
+}
+
+impl<T: Row> AnyTable for Table<T> {
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn clone_box(&self) -> Box<dyn AnyTable> {
+    Box::new(self.clone())
+  }
+
+  fn cell(&self, row: usize, col: usize) -> Option<CellValue> {
+    self.rows.get(row).and_then(|row| row.cell(col))
+  }
+
+  fn diff(&self, other: &dyn AnyTable) -> Option<TableDiff> {
+    other
+      .as_any()
+      .downcast_ref::<Table<T>>()
+      .map(|other| Table::diff(self, other))
+  }
+
+  fn headers(&self) -> Vec<String> {
+    Table::headers(self)
+  }
+
+  fn insert_values(&mut self, values: &[String]) -> Result<(), Error> {
+    if values.len() != T::column_count() {
+      return Err(Error::ColumnCountMismatch {
+        expected: T::column_count(),
+        actual: values.len(),
+      });
+    }
+
+    self.insert(T::from_values(values)?);
+
+    Ok(())
+  }
+
+  fn into_shared(self: Box<Self>) -> Rc<RefCell<dyn AnyTable>> {
+    Rc::new(RefCell::new(*self))
+  }
+
+  fn memory_estimate(&self) -> usize {
+    std::mem::size_of::<Table<T>>()
+      + self.rows.capacity() * std::mem::size_of::<T>()
+  }
+
+  fn records(&self) -> Vec<Vec<String>> {
+    Table::records(self)
+  }
+
+  fn restore(&mut self, snapshot: &dyn AnyTable) -> bool {
+    match snapshot.as_any().downcast_ref::<Table<T>>() {
+      Some(table) => {
+        *self = table.clone();
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn row_count(&self) -> usize {
+    self.rows.len()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    Table::set_name(self, name);
+  }
+
+  fn type_name(&self) -> &'static str {
+    std::any::type_name::<T>()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+  pub child_table: String,
+  pub child_column: String,
+  pub parent_table: String,
+  pub parent_column: String,
+}
+
+pub struct Savepoint {
+  foreign_keys: Vec<ForeignKey>,
+  tables: BTreeMap<String, Box<dyn AnyTable>>,
+}
+
+pub type Handle<T> = Rc<RefCell<Table<T>>>;
+
+#[derive(Default)]
+pub struct Database {
+  foreign_keys: Vec<ForeignKey>,
+  tables: BTreeMap<String, Rc<RefCell<dyn AnyTable>>>,
+}
+
+impl Database {
+  pub fn new() -> Self {
+    Self {
+      foreign_keys: Vec::new(),
+      tables: BTreeMap::new(),
+    }
+  }
+
+  fn column_values(
+    &self,
+    table: &str,
+    column: &str,
+  ) -> Result<Vec<String>, Error> {
+    let table = self
+      .tables
+      .get(table)
+      .ok_or_else(|| Error::TableNotFound(table.to_string()))?
+      .borrow();
+
+    let index = table
+      .headers()
+      .iter()
+      .position(|header| header == column)
+      .ok_or_else(|| Error::ColumnNotFound(column.to_string()))?;
+
+    Ok(
+      table
+        .records()
+        .into_iter()
+        .map(|mut record| {
+          record
+            .get_mut(index)
+            .map(std::mem::take)
+            .unwrap_or_default()
+        })
+        .collect(),
+    )
+  }
+
+  pub fn add_foreign_key(
+    &mut self,
+    child_table: &str,
+    child_column: &str,
+    parent_table: &str,
+    parent_column: &str,
+  ) -> Result<(), Error> {
+    self.column_values(child_table, child_column)?;
+    self.column_values(parent_table, parent_column)?;
+
+    self.foreign_keys.push(ForeignKey {
+      child_table: child_table.to_string(),
+      child_column: child_column.to_string(),
+      parent_table: parent_table.to_string(),
+      parent_column: parent_column.to_string(),
+    });
+
+    Ok(())
+  }
+
+  pub fn rename_table(&mut self, from: &str, to: &str) -> Result<(), Error> {
+    if from == to {
+      return if self.tables.contains_key(from) {
+        Ok(())
+      } else {
+        Err(Error::TableNotFound(from.to_string()))
+      };
+    }
+
+    self.ensure_available(to)?;
+
+    let table = self
+      .tables
+      .remove(from)
+      .ok_or_else(|| Error::TableNotFound(from.to_string()))?;
+
+    table.borrow_mut().set_name(to);
+
+    self.tables.insert(to.to_string(), table);
+
+    self.rename_foreign_keys(from, to);
+
+    Ok(())
+  }
+
+  fn rename_foreign_keys(&mut self, from: &str, to: &str) {
+    for foreign_key in &mut self.foreign_keys {
+      if foreign_key.child_table == from {
+        foreign_key.child_table = to.to_string();
+      }
+
+      if foreign_key.parent_table == from {
+        foreign_key.parent_table = to.to_string();
+      }
+    }
+  }
+
+  pub fn foreign_keys(&self) -> &[ForeignKey] {
+    &self.foreign_keys
+  }
+
+  /// Checks every registered foreign key in registration order, failing on the
+  /// first child row whose value is missing from the parent column. Empty
+  /// child values are treated as nulls and never violate a key.
+  pub fn check_foreign_keys(&self) -> Result<(), Error> {
+    for foreign_key in &self.foreign_keys {
+      let parent = self
+        .column_values(&foreign_key.parent_table, &foreign_key.parent_column)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+      let child = self
+        .column_values(&foreign_key.child_table, &foreign_key.child_column)?;
+
+      if let Some(row) = child
+        .iter()
+        .position(|value| !value.is_empty() && !parent.contains(value))
+      {
+        return Err(Error::ForeignKeyViolation {
+          table: foreign_key.child_table.clone(),
+          column: foreign_key.child_column.clone(),
+          row,
+        });
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tables.is_empty()
+  }
+
+  pub fn table_count(&self) -> usize {
+    self.tables.len()
+  }
+
+  pub fn create_table<T: Row + 'static>(
+    &mut self,
+    name: &str,
+  ) -> Result<Handle<T>, Error> {
+    self.store(Table::new(name.to_string()))
+  }
+
+  pub fn create_table_with_inserter<T: Row + 'static>(
+    &mut self,
+    name: &str,
+  ) -> Result<(Handle<T>, impl FnMut(T)), Error> {
+    let table = self.create_table::<T>(name)?;
+
+    let handle = table.clone();
+
+    Ok((table, move |row| handle.borrow_mut().insert(row)))
+  }
+
+  fn ensure_available(&self, name: &str) -> Result<(), Error> {
+    match self.tables.get(name) {
+      Some(table) => Err(Error::TableAlreadyExists(
+        name.to_string(),
+        table.borrow().type_name(),
+      )),
+      None => Ok(()),
+    }
+  }
+
+  pub fn store<T: Row + 'static>(
+    &mut self,
+    table: Table<T>,
+  ) -> Result<Handle<T>, Error> {
+    self.ensure_available(&table.name)?;
+
+    let name = table.name.clone();
+
+    let table = Rc::new(RefCell::new(table));
+
+    self.tables.insert(name, table.clone());
+
+    Ok(table)
+  }
+
+  pub fn import_json_into<T: Row + 'static>(
+    &mut self,
+    name: &str,
+    json: &str,
+    parse: impl Fn(&serde_json::Value) -> Result<T, Error>,
+  ) -> Result<Handle<T>, Error> {
+    self.ensure_available(name)?;
+
+    let value = serde_json::from_str::<serde_json::Value>(json)
+      .map_err(|error| Error::ParseError(error.to_string()))?;
+
+    let rows = value
+      .as_array()
+      .ok_or_else(|| {
+        Error::ParseError("expected a top-level JSON array".to_string())
+      })?
+      .iter()
+      .map(parse)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let table = self.create_table::<T>(name)?;
+
+    for row in rows {
+      table.borrow_mut().insert(row);
+    }
+
+    Ok(table)
+  }
+
+  /// Maps every row of `src` into a new table `dst`. When `drop_source` is set,
+  /// foreign keys on `src` follow it to `dst`, except those whose column no
+  /// longer exists in `U`, which are removed.
+  pub fn transform_table<T: Row + 'static, U: Row + 'static, F: Fn(&T) -> U>(
+    &mut self,
+    src: &str,
+    dst: &str,
+    f: F,
+    drop_source: bool,
+  ) -> Result<Handle<U>, Error> {
+    let rows = match self.tables.get(src) {
+      Some(table) => table
+        .borrow()
+        .as_any()
+        .downcast_ref::<Table<T>>()
+        .map(|table| table.rows.iter().map(&f).collect::<Vec<_>>())
+        .ok_or_else(|| Error::InvalidRowType(src.to_string()))?,
+      None => return Err(Error::TableNotFound(src.to_string())),
+    };
+
+    if !(drop_source && src == dst) {
+      self.ensure_available(dst)?;
+    }
+
+    if drop_source {
+      self.tables.remove(src);
+    }
+
+    let table = self.create_table::<U>(dst)?;
+
+    table.borrow_mut().rows = rows;
+
+    if drop_source {
+      let headers = table.borrow().headers();
+
+      let keeps = |table: &str, column: &str| {
+        table != src || headers.iter().any(|header| header == column)
+      };
+
+      self.foreign_keys.retain(|foreign_key| {
+        keeps(&foreign_key.child_table, &foreign_key.child_column)
+          && keeps(&foreign_key.parent_table, &foreign_key.parent_column)
+      });
+
+      self.rename_foreign_keys(src, dst);
+    }
+
+    Ok(table)
+  }
+
+  pub fn estimate_cross_join(&self, a: &str, b: &str) -> Result<usize, Error> {
+    let row_count = |name: &str| {
+      self
+        .tables
+        .get(name)
+        .map(|table| table.borrow().row_count())
+        .ok_or_else(|| Error::TableNotFound(name.to_string()))
+    };
+
+    Ok(row_count(a)?.saturating_mul(row_count(b)?))
+  }
+
+  pub fn cross_join<T: Row + 'static, U: Row + 'static>(
+    &self,
+    table_a: &Table<T>,
+    table_b: &Table<U>,
+  ) -> Table<JoinedRow<T, U>> {
+    self.cross_join_limited(table_a, table_b, usize::MAX)
+  }
+
+  /// Like `cross_join`, but stops after `max` rows. Rows are produced in the
+  /// same order as `cross_join`, every right row for the first left row before
+  /// moving to the next, so the truncated result is deterministic.
+  pub fn cross_join_limited<T: Row + 'static, U: Row + 'static>(
+    &self,
+    table_a: &Table<T>,
+    table_b: &Table<U>,
+    max: usize,
+  ) -> Table<JoinedRow<T, U>> {
+    let mut joined_table =
+      Table::new(format!("{}_cross_{}", table_a.name, table_b.name));
+
+    joined_table.join_sources =
+      Some((table_a.name.clone(), table_b.name.clone()));
+
+    let pairs = table_a
+      .rows
+      .iter()
+      .flat_map(|left_row| {
+        table_b
+          .rows
+          .iter()
+          .map(move |right_row| (left_row, right_row))
+      })
+      .take(max);
+
+    for (left_row, right_row) in pairs {
+      joined_table.insert(JoinedRow {
+        left: left_row.clone(),
+        right: right_row.clone(),
+      });
+    }
+
+    joined_table
+  }
+
+  pub fn left_join<T: Row + 'static, U: Row + 'static>(
+    &self,
+    table_a: &Table<T>,
+    table_b: &Table<U>,
+    predicate: impl Fn(&T, &U) -> bool,
+  ) -> Table<OuterJoinedRow<T, U>> {
+    let mut joined_table =
+      Table::new(format!("{}_left_{}", table_a.name, table_b.name));
+
+    for left_row in &table_a.rows {
+      let mut matched = false;
+
+      for right_row in &table_b.rows {
+        if predicate(left_row, right_row) {
+          matched = true;
+
+          joined_table.insert(OuterJoinedRow {
+            left: left_row.clone(),
+            right: Some(right_row.clone()),
+          });
+        }
+      }
+
+      if !matched {
+        joined_table.insert(OuterJoinedRow {
+          left: left_row.clone(),
+          right: None,
+        });
+      }
+    }
+
+    joined_table
+  }
+
+  pub fn diff(&self, other: &Database) -> DatabaseDiff {
+    let mut diff = DatabaseDiff {
+      only_in_other: other
+        .tables
+        .keys()
+        .filter(|name| !self.tables.contains_key(*name))
+        .cloned()
+        .collect(),
+      ..DatabaseDiff::default()
+    };
+
+    for (name, table) in &self.tables {
+      let Some(other_table) = other.tables.get(name) else {
+        diff.only_in_self.push(name.clone());
+        continue;
+      };
+
+      match table.borrow().diff(&*other_table.borrow()) {
+        Some(table_diff) if table_diff.is_empty() => {}
+        Some(table_diff) => {
+          diff.changed.insert(name.clone(), table_diff);
+        }
+        None => diff.type_mismatches.push(name.clone()),
+      }
+    }
+
+    diff
+  }
+
+  pub fn execute(&mut self, sql: &str) -> Result<String, Error> {
+    let (headers, records) = match sql::parse(sql)? {
+      sql::Statement::Insert { table, values } => {
+        self
+          .tables
+          .get(&table)
+          .ok_or_else(|| Error::TableNotFound(table.clone()))?
+          .borrow_mut()
+          .insert_values(&values)?;
+
+        (vec!["Inserted".to_string()], vec![vec!["1".to_string()]])
+      }
+      sql::Statement::Select { .. } => self.query(sql)?,
+    };
+
+    Ok(render(&headers, &records))
+  }
+
+  pub fn query(&self, sql: &str) -> Result<Records, Error> {
+    match sql::parse(sql)? {
+      sql::Statement::Select {
+        projection,
+        table,
+        order_by,
+      } => self.select(projection, &table, order_by),
+      sql::Statement::Insert { .. } => Err(Error::Unsupported(
+        "query only runs SELECT statements, use execute instead".to_string(),
+      )),
+    }
+  }
+
+  fn select(
+    &self,
+    projection: sql::Projection,
+    table: &str,
+    order_by: Option<(String, bool)>,
+  ) -> Result<Records, Error> {
+    let table = self
+      .tables
+      .get(table)
+      .ok_or_else(|| Error::TableNotFound(table.to_string()))?
+      .borrow();
+
+    let headers = table.headers();
+
+    let column_index = |column: String| {
+      headers
+        .iter()
+        .position(|header| *header == column)
+        .ok_or(Error::ColumnNotFound(column))
+    };
+
+    match projection {
+      sql::Projection::Count => {
+        if order_by.is_some() {
+          return Err(Error::Unsupported(
+            "ORDER BY cannot be combined with COUNT(*)".to_string(),
+          ));
+        }
+
+        Ok((
+          vec!["COUNT(*)".to_string()],
+          vec![vec![table.row_count().to_string()]],
+        ))
+      }
+      sql::Projection::GroupCount(column) => {
+        if order_by.is_some() {
+          return Err(Error::Unsupported(
+            "ORDER BY cannot be combined with GROUP BY".to_string(),
+          ));
+        }
+
+        let col = column_index(column.clone())?;
+
+        let mut records = table.records();
+
+        let mut cells = (0..table.row_count())
+          .map(|row| {
+            (
+              table.cell(row, col).unwrap_or(CellValue::Null),
+              records[row]
+                .get_mut(col)
+                .map(std::mem::take)
+                .unwrap_or_default(),
+            )
+          })
+          .collect::<Vec<_>>();
+
+        cells.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut groups: Vec<(CellValue, String, usize)> = Vec::new();
+
+        for (cell, value) in cells {
+          match groups.last_mut() {
+            Some((last, _, count)) if last.total_cmp(&cell).is_eq() => {
+              *count += 1;
+            }
+            _ => groups.push((cell, value, 1)),
+          }
+        }
+
+        Ok((
+          vec![column, "COUNT(*)".to_string()],
+          groups
+            .into_iter()
+            .map(|(_, value, count)| vec![value, count.to_string()])
+            .collect(),
+        ))
+      }
+      sql::Projection::All => {
+        let mut order = (0..table.row_count()).collect::<Vec<_>>();
+
+        if let Some((column, ascending)) = order_by {
+          let col = column_index(column)?;
+
+          let cells = order
+            .iter()
+            .map(|&row| table.cell(row, col).unwrap_or(CellValue::Null))
+            .collect::<Vec<_>>();
+
+          order.sort_by(|&a, &b| {
+            let ordering = cells[a].total_cmp(&cells[b]);
+
+            if ascending {
+              ordering
+            } else {
+              ordering.reverse()
+            }
+          });
+        }
+
+        let mut records = table.records();
+
+        let records = order
+          .into_iter()
+          .map(|row| std::mem::take(&mut records[row]))
+          .collect();
+
+        Ok((headers, records))
+      }
+    }
+  }
+
+  /// Estimates each table's size in bytes as the table itself plus its row
+  /// buffer's full capacity. Heap data owned by rows, such as string contents,
+  /// and index storage are not counted, so treat the numbers as lower bounds
+  /// for comparing tables rather than exact usage.
+  pub fn memory_estimate(&self) -> BTreeMap<String, usize> {
+    self
+      .tables
+      .iter()
+      .map(|(name, table)| (name.clone(), table.borrow().memory_estimate()))
+      .collect()
+  }
+
+  pub fn savepoint(&self) -> Savepoint {
+    Savepoint {
+      foreign_keys: self.foreign_keys.clone(),
+      tables: self
+        .tables
+        .iter()
+        .map(|(name, table)| (name.clone(), table.borrow().clone_box()))
+        .collect(),
+    }
+  }
+
+  pub fn rollback_to(&mut self, savepoint: Savepoint) {
+    self.foreign_keys = savepoint.foreign_keys;
+
+    self
+      .tables
+      .retain(|name, _| savepoint.tables.contains_key(name));
+
+    for (name, snapshot) in savepoint.tables {
+      let restored = self
+        .tables
+        .get(&name)
+        .is_some_and(|table| table.borrow_mut().restore(snapshot.as_ref()));
+
+      if !restored {
+        self.tables.insert(name, snapshot.into_shared());
+      }
+    }
+  }
+
+  pub fn snapshot_table<T: Row + 'static>(
+    &self,
+    name: &str,
+  ) -> Result<Table<T>, Error> {
+    match self.tables.get(name) {
+      Some(table) => table
+        .borrow()
+        .as_any()
+        .downcast_ref::<Table<T>>()
+        .cloned()
+        .ok_or_else(|| Error::InvalidRowType(name.to_string())),
+      None => Err(Error::TableNotFound(name.to_string())),
+    }
+  }
+
+  #[cfg(test)]
+  pub fn from<T: Row + 'static>(
+    &self,
+    table_name: &str,
+  ) -> Result<Handle<T>, Error> {
+    self
+      .snapshot_table(table_name)
+      .map(|table| Rc::new(RefCell::new(table)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    prettytable::row,
+    rand::{rngs::StdRng, SeedableRng},
+  };
+
+  #[derive(Debug, Clone, PartialEq)]
+  struct Book {
+    id: u32,
+    name: String,
+    author_id: u32,
+  }
+
+  impl Row for Book {
+    fn header() -> PrettyRow {
+      row!["ID", "Name", "Author ID"]
+    }
+
+    fn to_pretty_row(&self) -> PrettyRow {
+      row![self.id, self.name, self.author_id]
+    }
+
+    fn from_values(values: &[String]) -> Result<Self, Error> {
+      parse_book(values)
+    }
+
+    fn cell(&self, col: usize) -> Option<CellValue> {
+      match col {
+        0 => Some(CellValue::Int(self.id.into())),
+        1 => Some(CellValue::Text(self.name.clone())),
+        2 => Some(CellValue::Int(self.author_id.into())),
+        _ => None,
+      }
+    }
+  }
+
+  #[derive(Debug, Clone, PartialEq)]
+  struct Author {
+    id: u32,
+    name: String,
+  }
+
+  impl Row for Author {
+    fn header() -> PrettyRow {
+      row!["ID", "Name"]
+    }
+
+    fn to_pretty_row(&self) -> PrettyRow {
+      row![self.id, self.name]
+    }
+
+    fn null_row() -> PrettyRow {
+      row!["N/A", "N/A"]
+    }
+  }
+
+  #[derive(Debug, Clone, PartialEq)]
+  struct Title {
+    name: String,
+  }
+
+  impl Row for Title {
+    fn header() -> PrettyRow {
+      row!["Name"]
+    }
+
+    fn to_pretty_row(&self) -> PrettyRow {
+      row![self.name]
+    }
+  }
+
+  #[derive(Debug, Clone, PartialEq)]
+  struct Tag {
+    name: String,
+    parent: Option<String>,
+  }
+
+  impl Row for Tag {
+    fn header() -> PrettyRow {
+      row!["Name", "Parent"]
+    }
+
+    fn to_pretty_row(&self) -> PrettyRow {
+      match &self.parent {
+        Some(parent) => row![self.name, parent],
+        None => row![self.name],
+      }
+    }
+  }
+
+  #[test]
+  fn create_table() {
+    let mut db = Database::new();
+
+    assert!(db.create_table::<Book>("books").is_ok());
+
+    let err = db.create_table::<Book>("books");
+
+    assert!(matches!(err, Err(Error::TableAlreadyExists(..))));
+  }
+
+  #[test]
+  fn insert_single() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "Test Book".to_string(),
+      author_id: 1,
+    });
+
+    let table = db.from::<Book>("books").unwrap();
+
+    assert_eq!(table.borrow().rows.len(), 1);
+
+    assert_eq!(table.borrow().rows[0].name, "Test Book");
+  }
+
+  #[test]
+  fn insert_many() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "Book 1".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Book 2".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    let table = db.from::<Book>("books").unwrap();
+
+    assert_eq!(table.borrow().rows.len(), 2);
+
+    assert_eq!(table.borrow().rows[0].name, "Book 1");
+
+    assert_eq!(table.borrow().rows[1].name, "Book 2");
+  }
+
+  #[test]
+  fn cross_join() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    authors.borrow_mut().insert_many(&[
+      Author {
+        id: 1,
+        name: "George Orwell".to_string(),
+      },
+      Author {
+        id: 2,
+        name: "Aldous Huxley".to_string(),
+      },
+    ]);
+
+    let joined_table = db.cross_join(&books.borrow(), &authors.borrow());
+
+    assert_eq!(joined_table.rows.len(), 4);
 
     let first_row = &joined_table.rows[0];
 
-    assert_eq!(first_row.left.id, 1);
-    assert_eq!(first_row.left.name, "1984");
-    assert_eq!(first_row.left.author_id, 1);
-    assert_eq!(first_row.right.id, 1);
-    assert_eq!(first_row.right.name, "George Orwell");
+    assert_eq!(first_row.left.id, 1);
+    assert_eq!(first_row.left.name, "1984");
+    assert_eq!(first_row.left.author_id, 1);
+    assert_eq!(first_row.right.id, 1);
+    assert_eq!(first_row.right.name, "George Orwell");
+
+    let last_row = &joined_table.rows[3];
+
+    assert_eq!(last_row.left.id, 2);
+    assert_eq!(last_row.left.name, "Animal Farm");
+    assert_eq!(last_row.left.author_id, 1);
+    assert_eq!(last_row.right.id, 2);
+    assert_eq!(last_row.right.name, "Aldous Huxley");
+  }
+
+  #[test]
+  fn sort_by_column_name() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 3,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    let sorted = books.borrow().sort_by_column_name("Name", true).unwrap();
+
+    assert_eq!(
+      sorted.rows.iter().map(|row| row.id).collect::<Vec<_>>(),
+      vec![3, 2, 1]
+    );
+
+    let sorted = books.borrow().sort_by_column_name("Name", false).unwrap();
+
+    assert_eq!(
+      sorted.rows.iter().map(|row| row.id).collect::<Vec<_>>(),
+      vec![1, 2, 3]
+    );
+  }
+
+  #[test]
+  fn sort_by_unknown_column_name() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    assert!(matches!(
+      books.borrow().sort_by_column_name("Title", true),
+      Err(Error::ColumnNotFound(title)) if title == "Title"
+    ));
+  }
+
+  #[test]
+  fn select_columns() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    let (headers, records) = books.borrow().select_columns(&["Name"]).unwrap();
+
+    assert_eq!(headers, vec!["Name"]);
+
+    assert_eq!(records, vec![vec!["1984"], vec!["Animal Farm"]]);
+
+    let rendered = render(&headers, &records);
+
+    assert!(rendered.contains("Animal Farm"));
+
+    assert!(!rendered.contains("Author ID"));
+
+    assert!(matches!(
+      books.borrow().select_columns(&["Name", "Title"]),
+      Err(Error::ColumnNotFound(title)) if title == "Title"
+    ));
+  }
+
+  #[test]
+  fn with_computed_column() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    let (headers, records) =
+      books.borrow().with_computed_column("Slug", |book| {
+        book.name.to_lowercase().replace(' ', "-")
+      });
+
+    assert_eq!(headers, vec!["ID", "Name", "Author ID", "Slug"]);
+
+    assert_eq!(records[0], vec!["1", "Animal Farm", "1", "animal-farm"]);
+
+    assert_eq!(records[1][3], "brave-new-world");
+  }
+
+  #[test]
+  fn rollback_to_savepoint() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    let savepoint = db.savepoint();
+
+    books.borrow_mut().insert(Book {
+      id: 2,
+      name: "Animal Farm".to_string(),
+      author_id: 1,
+    });
+
+    db.create_table::<Author>("authors").unwrap();
+
+    db.rollback_to(savepoint);
+
+    assert_eq!(books.borrow().rows.len(), 1);
+
+    assert_eq!(books.borrow().rows[0].name, "1984");
+
+    assert!(matches!(
+      db.from::<Author>("authors"),
+      Err(Error::TableNotFound(_))
+    ));
+  }
+
+  #[test]
+  fn rollback_to_savepoint_with_foreign_keys() {
+    let mut db = Database::new();
+
+    db.create_table::<Book>("books").unwrap();
+
+    let savepoint = db.savepoint();
+
+    db.create_table::<Author>("authors").unwrap();
+
+    db.add_foreign_key("books", "Author ID", "authors", "ID")
+      .unwrap();
+
+    db.rollback_to(savepoint);
+
+    assert!(db.foreign_keys().is_empty());
+
+    assert!(db.check_foreign_keys().is_ok());
+  }
+
+  #[test]
+  fn rollback_to_nested_savepoint() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    let outer = db.savepoint();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    let inner = db.savepoint();
+
+    books.borrow_mut().insert(Book {
+      id: 2,
+      name: "Animal Farm".to_string(),
+      author_id: 1,
+    });
+
+    db.rollback_to(inner);
+
+    assert_eq!(books.borrow().rows.len(), 1);
+
+    db.rollback_to(outer);
+
+    assert!(books.borrow().rows.is_empty());
+  }
+
+  #[test]
+  fn indices_by_key() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    books
+      .borrow_mut()
+      .create_index("author_id", |book: &Book| book.author_id);
+
+    books.borrow_mut().create_index("id", |book: &Book| book.id);
+
+    books.borrow_mut().insert(Book {
+      id: 3,
+      name: "Animal Farm".to_string(),
+      author_id: 1,
+    });
+
+    assert_eq!(books.borrow().indices_by_key("author_id", &1u32), &[0, 2]);
+
+    assert_eq!(books.borrow().indices_by_key("author_id", &2u32), &[1]);
+
+    assert!(books.borrow().indices_by_key("author_id", &3u32).is_empty());
+
+    assert_eq!(books.borrow().indices_by_key("id", &1u32), &[0]);
+
+    assert_eq!(books.borrow().indices_by_key("id", &3u32), &[2]);
+
+    assert!(books.borrow().indices_by_key("name", &1u32).is_empty());
+
+    assert!(books.borrow().indices_by_key("id", &1u64).is_empty());
+  }
+
+  #[test]
+  fn chained_construction() {
+    let table = Table::from_rows(
+      "books",
+      vec![Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      }],
+    )
+    .named("classics")
+    .with_format(*format::consts::FORMAT_CLEAN);
+
+    assert_eq!(table.name, "classics");
+
+    assert_eq!(table.rows.len(), 1);
+
+    assert!(!table.to_string().contains('│'));
+  }
+
+  #[test]
+  fn import_json_into() {
+    let mut db = Database::new();
+
+    let parse = |value: &serde_json::Value| {
+      Ok(Book {
+        id: value["id"]
+          .as_u64()
+          .ok_or_else(|| Error::ParseError("missing id".to_string()))?
+          as u32,
+        name: value["name"]
+          .as_str()
+          .ok_or_else(|| Error::ParseError("missing name".to_string()))?
+          .to_string(),
+        author_id: value["author_id"]
+          .as_u64()
+          .ok_or_else(|| Error::ParseError("missing author_id".to_string()))?
+          as u32,
+      })
+    };
+
+    let books = db
+      .import_json_into(
+        "books",
+        r#"[
+          { "id": 1, "name": "1984", "author_id": 1 },
+          { "id": 2, "name": "Animal Farm", "author_id": 1 }
+        ]"#,
+        parse,
+      )
+      .unwrap();
+
+    assert_eq!(books.borrow().rows.len(), 2);
+
+    assert_eq!(books.borrow().rows[1].name, "Animal Farm");
+
+    assert!(matches!(
+      db.import_json_into("books", "[]", parse),
+      Err(Error::TableAlreadyExists(..))
+    ));
+
+    assert!(matches!(
+      db.import_json_into("others", r#"{ "id": 1 }"#, parse),
+      Err(Error::ParseError(_))
+    ));
+
+    assert!(matches!(
+      db.import_json_into("others", r#"[{ "id": 1 }]"#, parse),
+      Err(Error::ParseError(_))
+    ));
+
+    assert!(matches!(
+      db.from::<Book>("others"),
+      Err(Error::TableNotFound(_))
+    ));
+  }
+
+  #[test]
+  fn key_skew() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 3,
+        name: "Homage to Catalonia".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 4,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    assert_eq!(books.borrow().key_skew(|book| book.author_id), (2, 3, 2.0));
+
+    assert_eq!(
+      db.create_table::<Author>("authors")
+        .unwrap()
+        .borrow()
+        .key_skew(|author| author.id),
+      (0, 0, 0.0)
+    );
+  }
+
+  #[test]
+  fn left_join() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    authors.borrow_mut().insert(Author {
+      id: 1,
+      name: "George Orwell".to_string(),
+    });
+
+    let joined_table =
+      db.left_join(&books.borrow(), &authors.borrow(), |book, author| {
+        book.author_id == author.id
+      });
+
+    assert_eq!(joined_table.rows.len(), 2);
+
+    assert_eq!(
+      joined_table.rows[0].right.as_ref().unwrap().name,
+      "George Orwell"
+    );
+
+    assert!(joined_table.rows[1].right.is_none());
+
+    assert_eq!(
+      joined_table.rows[1].values(),
+      vec!["2", "Brave New World", "2", "N/A", "N/A"]
+    );
+
+    assert!(joined_table.to_string().contains("N/A"));
+  }
+
+  #[test]
+  fn insert_dedup() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    let inserted = books.borrow_mut().insert_dedup(
+      vec![
+        Book {
+          id: 2,
+          name: "Animal Farm".to_string(),
+          author_id: 1,
+        },
+        Book {
+          id: 3,
+          name: "Brave New World".to_string(),
+          author_id: 2,
+        },
+        Book {
+          id: 1,
+          name: "1984".to_string(),
+          author_id: 1,
+        },
+        Book {
+          id: 4,
+          name: "Island".to_string(),
+          author_id: 2,
+        },
+      ],
+      |book| book.id,
+    );
+
+    assert_eq!(inserted, 2);
+
+    assert_eq!(
+      books
+        .borrow()
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![1, 2, 3, 4]
+    );
+  }
+
+  #[test]
+  fn database_diff() {
+    let mut before = Database::new();
+
+    before
+      .create_table::<Book>("books")
+      .unwrap()
+      .borrow_mut()
+      .insert_many(&[
+        Book {
+          id: 1,
+          name: "1984".to_string(),
+          author_id: 1,
+        },
+        Book {
+          id: 2,
+          name: "Animal Farm".to_string(),
+          author_id: 1,
+        },
+      ]);
+
+    before.create_table::<Author>("authors").unwrap();
+    before.create_table::<Book>("reviews").unwrap();
+
+    let mut after = Database::new();
+
+    after
+      .create_table::<Book>("books")
+      .unwrap()
+      .borrow_mut()
+      .insert_many(&[
+        Book {
+          id: 1,
+          name: "1984".to_string(),
+          author_id: 1,
+        },
+        Book {
+          id: 3,
+          name: "Brave New World".to_string(),
+          author_id: 2,
+        },
+      ]);
+
+    after.create_table::<Author>("publishers").unwrap();
+    after.create_table::<Author>("reviews").unwrap();
+
+    let diff = before.diff(&after);
+
+    assert_eq!(diff.only_in_self, vec!["authors"]);
+
+    assert_eq!(diff.only_in_other, vec!["publishers"]);
+
+    assert_eq!(diff.type_mismatches, vec!["reviews"]);
+
+    assert_eq!(
+      diff.changed["books"],
+      TableDiff {
+        only_in_self: vec![vec![
+          "2".to_string(),
+          "Animal Farm".to_string(),
+          "1".to_string()
+        ]],
+        only_in_other: vec![vec![
+          "3".to_string(),
+          "Brave New World".to_string(),
+          "2".to_string()
+        ]],
+      }
+    );
+
+    assert!(before.diff(&before).is_empty());
+  }
+
+  #[test]
+  fn to_fixed_width() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    assert_eq!(
+      books.borrow().to_fixed_width(&[3, 8, 6]).unwrap(),
+      "ID Name    Author\n1  1984    1     \n2  Brave Ne2     \n"
+    );
+
+    assert!(matches!(
+      books.borrow().to_fixed_width(&[3, 8]),
+      Err(Error::ColumnCountMismatch {
+        expected: 3,
+        actual: 2
+      })
+    ));
+  }
+
+  #[test]
+  fn merge_sorted() {
+    let a = Table::from_rows(
+      "books",
+      [1, 4, 5]
+        .into_iter()
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: 1,
+        })
+        .collect(),
+    );
+
+    let b = Table::from_rows(
+      "books",
+      [2, 3, 6, 7]
+        .into_iter()
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: 2,
+        })
+        .collect(),
+    );
+
+    let merged = a.merge_sorted(&b, |book| book.id);
+
+    assert_eq!(
+      merged.rows.iter().map(|book| book.id).collect::<Vec<_>>(),
+      vec![1, 2, 3, 4, 5, 6, 7]
+    );
+  }
+
+  #[test]
+  fn transform_table() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    let to_title = |book: &Book| Title {
+      name: book.name.clone(),
+    };
+
+    let titles = db
+      .transform_table("books", "titles", to_title, true)
+      .unwrap();
+
+    assert_eq!(
+      titles.borrow().rows,
+      vec![
+        Title {
+          name: "1984".to_string()
+        },
+        Title {
+          name: "Animal Farm".to_string()
+        }
+      ]
+    );
+
+    assert_eq!(db.from::<Title>("titles").unwrap().borrow().rows.len(), 2);
+
+    assert!(matches!(
+      db.from::<Book>("books"),
+      Err(Error::TableNotFound(_))
+    ));
+
+    assert!(matches!(
+      db.transform_table("books", "others", to_title, false),
+      Err(Error::TableNotFound(_))
+    ));
+
+    assert!(matches!(
+      db.transform_table("titles", "others", to_title, false),
+      Err(Error::InvalidRowType(_))
+    ));
+
+    db.create_table::<Book>("books").unwrap();
+
+    assert!(matches!(
+      db.transform_table("books", "titles", to_title, false),
+      Err(Error::TableAlreadyExists(..))
+    ));
+  }
+
+  #[test]
+  fn filter() {
+    let books = Table::from_rows(
+      "books",
+      (1..=6)
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: id % 2,
+        })
+        .collect(),
+    );
+
+    let filtered = books.filter(|book| book.author_id == 0);
+
+    assert_eq!(
+      filtered.rows.iter().map(|book| book.id).collect::<Vec<_>>(),
+      vec![2, 4, 6]
+    );
+  }
+
+  #[cfg(feature = "rayon")]
+  #[test]
+  fn par_filter() {
+    let books = Table::from_rows(
+      "books",
+      (1..=1000)
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: id % 7,
+        })
+        .collect(),
+    );
+
+    let predicate =
+      |book: &Book| book.author_id == 3 || book.name.ends_with('0');
+
+    assert_eq!(
+      books.par_filter(predicate).rows,
+      books.filter(predicate).rows
+    );
+  }
+
+  #[test]
+  fn write_csv() {
+    let books = Table::from_rows(
+      "books",
+      vec![
+        Book {
+          id: 1,
+          name: "1984".to_string(),
+          author_id: 1,
+        },
+        Book {
+          id: 2,
+          name: "Brave New World, Revisited".to_string(),
+          author_id: 2,
+        },
+      ],
+    );
+
+    let mut output = Vec::new();
+
+    books.write_csv(&mut output).unwrap();
+
+    assert_eq!(output, books.to_csv().unwrap().into_bytes());
+
+    assert_eq!(
+      books.to_csv().unwrap(),
+      "ID,Name,Author ID\n1,1984,1\n2,\"Brave New World, Revisited\",2\n"
+    );
+  }
+
+  #[test]
+  fn store() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    authors.borrow_mut().insert(Author {
+      id: 1,
+      name: "George Orwell".to_string(),
+    });
+
+    let joined_table = db.cross_join(&books.borrow(), &authors.borrow());
+
+    db.store(joined_table.clone()).unwrap();
+
+    let stored = db
+      .from::<JoinedRow<Book, Author>>("books_cross_authors")
+      .unwrap();
+
+    assert_eq!(stored.borrow().rows.len(), 1);
+
+    assert_eq!(stored.borrow().rows[0].right.name, "George Orwell");
+
+    assert!(matches!(
+      db.store(joined_table),
+      Err(Error::TableAlreadyExists(..))
+    ));
+  }
+
+  #[test]
+  fn sort_dedup_by_key() {
+    let books = Table::from_rows(
+      "books",
+      [
+        (3, "Island"),
+        (1, "1984"),
+        (3, "Brave New World"),
+        (2, "Animal Farm"),
+      ]
+      .into_iter()
+      .map(|(id, name)| Book {
+        id,
+        name: name.to_string(),
+        author_id: 1,
+      })
+      .collect(),
+    );
+
+    let deduped = books.sort_dedup_by_key(|book| book.id);
+
+    assert_eq!(
+      deduped
+        .rows
+        .iter()
+        .map(|book| (book.id, book.name.as_str()))
+        .collect::<Vec<_>>(),
+      vec![(1, "1984"), (2, "Animal Farm"), (3, "Island")]
+    );
+  }
+
+  #[test]
+  fn table_count() {
+    let mut db = Database::new();
+
+    assert!(db.is_empty());
+
+    assert_eq!(db.table_count(), 0);
+
+    db.create_table::<Book>("books").unwrap();
+    db.create_table::<Author>("authors").unwrap();
+
+    assert!(!db.is_empty());
+
+    assert_eq!(db.table_count(), 2);
+  }
+
+  #[test]
+  fn cell() {
+    let book = Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 2,
+    };
+
+    assert_eq!(book.cell(0), Some(CellValue::Int(1)));
+
+    assert_eq!(book.cell(1), Some(CellValue::Text("1984".to_string())));
+
+    assert_eq!(book.cell(3), None);
+
+    let author = Author {
+      id: 1,
+      name: String::new(),
+    };
+
+    assert_eq!(author.cell(0), Some(CellValue::Text("1".to_string())));
+
+    assert_eq!(author.cell(1), Some(CellValue::Null));
+  }
+
+  #[test]
+  fn order_by_cell() {
+    let books = Table::from_rows(
+      "books",
+      [10, 2, 1, 21]
+        .into_iter()
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: 1,
+        })
+        .collect(),
+    );
+
+    assert_eq!(
+      books
+        .sort_by_column_name("ID", true)
+        .unwrap()
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![1, 10, 2, 21]
+    );
+
+    assert_eq!(
+      books
+        .order_by_cell(0, true)
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![1, 2, 10, 21]
+    );
+
+    assert_eq!(
+      books
+        .order_by_cell(0, false)
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![21, 10, 2, 1]
+    );
+  }
+
+  #[test]
+  fn filter_cell() {
+    let books = Table::from_rows(
+      "books",
+      [(3, "1984"), (6, "Animal Farm"), (9, "1984")]
+        .into_iter()
+        .map(|(id, name)| Book {
+          id,
+          name: name.to_string(),
+          author_id: 1,
+        })
+        .collect(),
+    );
+
+    let ids = |table: Table<Book>| {
+      table.rows.iter().map(|book| book.id).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      ids(books.filter_cell(0, CompareOp::Gt, CellValue::Int(5))),
+      vec![6, 9]
+    );
+
+    assert_eq!(
+      ids(books.filter_cell(0, CompareOp::Le, CellValue::Float(6.0))),
+      vec![3, 6]
+    );
+
+    assert_eq!(
+      ids(books.filter_cell(
+        1,
+        CompareOp::Eq,
+        CellValue::Text("1984".to_string())
+      )),
+      vec![3, 9]
+    );
+
+    assert!(books
+      .filter_cell(0, CompareOp::Ne, CellValue::Text("3".to_string()))
+      .rows
+      .is_empty());
+
+    assert!(books
+      .filter_cell(0, CompareOp::Ne, CellValue::Null)
+      .rows
+      .is_empty());
+  }
+
+  #[test]
+  fn execute() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 10,
+        name: "Island".to_string(),
+        author_id: 2,
+      },
+      Book {
+        id: 2,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 1,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    assert_eq!(
+      db.execute("SELECT COUNT(*) FROM books").unwrap(),
+      render(&["COUNT(*)".to_string()], &[vec!["3".to_string()]])
+    );
+
+    assert_eq!(
+      db.execute("SELECT * FROM books ORDER BY ID").unwrap(),
+      books.borrow().order_by_cell(0, true).to_string()
+    );
+
+    assert_eq!(
+      db.execute("SELECT * FROM books ORDER BY ID DESC").unwrap(),
+      books.borrow().order_by_cell(0, false).to_string()
+    );
+
+    assert!(matches!(
+      db.execute("SELECT * FROM authors"),
+      Err(Error::TableNotFound(_))
+    ));
+
+    assert!(matches!(
+      db.execute("SELECT * FROM books ORDER BY Title"),
+      Err(Error::ColumnNotFound(_))
+    ));
+
+    assert!(matches!(
+      db.execute("SELECT COUNT(*) FROM books ORDER BY Bogus"),
+      Err(Error::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn reorder_columns_to() {
+    let authors = Table::from_rows(
+      "authors",
+      vec![Author {
+        id: 1,
+        name: "George Orwell".to_string(),
+      }],
+    );
+
+    let (headers, records) =
+      authors.reorder_columns_to(&["Name", "ID"]).unwrap();
+
+    assert_eq!(headers, vec!["Name", "ID"]);
+
+    assert_eq!(records, vec![vec!["George Orwell", "1"]]);
+
+    assert!(matches!(
+      authors.reorder_columns_to(&["Name", "Born"]),
+      Err(Error::ColumnNotFound(title)) if title == "Born"
+    ));
+  }
+
+  #[test]
+  fn stratified_sample() {
+    let books = Table::from_rows(
+      "books",
+      (1..=100)
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: if id <= 80 { 1 } else { 2 },
+        })
+        .collect(),
+    );
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let sample = books.stratified_sample(|book| book.author_id, 0.25, &mut rng);
+
+    let count = |author_id| {
+      sample
+        .rows
+        .iter()
+        .filter(|book| book.author_id == author_id)
+        .count()
+    };
+
+    assert_eq!((count(1), count(2)), (20, 5));
+
+    assert!(sample.rows.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    assert_eq!(
+      books
+        .stratified_sample(|book| book.author_id, 2.0, &mut rng)
+        .rows
+        .len(),
+      100
+    );
+
+    assert!(books
+      .stratified_sample(|book| book.author_id, -1.0, &mut rng)
+      .rows
+      .is_empty());
+  }
+
+  #[test]
+  fn table_already_exists_type_name() {
+    let mut db = Database::new();
+
+    db.create_table::<Book>("books").unwrap();
+
+    let err = db.create_table::<Author>("books").unwrap_err();
+
+    assert!(matches!(
+      &err,
+      Error::TableAlreadyExists(name, type_name)
+        if name == "books" && *type_name == std::any::type_name::<Book>()
+    ));
+
+    assert!(err.to_string().contains("Book"));
+  }
+
+  #[test]
+  fn bucket_by() {
+    let books = Table::from_rows(
+      "books",
+      [0, 3599, 3600, 7300, -1, -3600, -3601]
+        .into_iter()
+        .enumerate()
+        .map(|(id, timestamp)| Book {
+          id: id as u32,
+          name: timestamp.to_string(),
+          author_id: 1,
+        })
+        .collect(),
+    );
+
+    let buckets =
+      books.bucket_by(3600, |book| book.name.parse::<i64>().unwrap());
+
+    assert_eq!(
+      buckets,
+      BTreeMap::from([
+        (-2, vec![6]),
+        (-1, vec![4, 5]),
+        (0, vec![0, 1]),
+        (1, vec![2]),
+        (2, vec![3]),
+      ])
+    );
+  }
+
+  #[test]
+  fn assert_no_nulls() {
+    let authors = Table::from_rows(
+      "authors",
+      vec![
+        Author {
+          id: 1,
+          name: "George Orwell".to_string(),
+        },
+        Author {
+          id: 2,
+          name: String::new(),
+        },
+      ],
+    );
+
+    assert!(authors.assert_no_nulls(&[0]).is_ok());
+
+    assert!(matches!(
+      authors.assert_no_nulls(&[0, 1]),
+      Err(Error::NullViolation { row: 1, column: 1 })
+    ));
+  }
+
+  #[test]
+  fn keep_max_by() {
+    let books = Table::from_rows(
+      "books",
+      [(1, 3), (2, 5), (1, 7), (2, 5), (3, 1), (1, 2)]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (id, version))| Book {
+          id,
+          name: format!("Revision {i}"),
+          author_id: version,
+        })
+        .collect(),
+    );
+
+    let latest = books.keep_max_by(|book| book.id, |book| book.author_id);
+
+    assert_eq!(
+      latest
+        .rows
+        .iter()
+        .map(|book| book.name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["Revision 1", "Revision 2", "Revision 4"]
+    );
+  }
+
+  #[test]
+  fn check_foreign_keys() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      },
+    ]);
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    authors.borrow_mut().insert_many(&[
+      Author {
+        id: 1,
+        name: "George Orwell".to_string(),
+      },
+      Author {
+        id: 2,
+        name: "Aldous Huxley".to_string(),
+      },
+    ]);
+
+    db.add_foreign_key("books", "Author ID", "authors", "ID")
+      .unwrap();
+
+    assert!(db.check_foreign_keys().is_ok());
+
+    books.borrow_mut().insert(Book {
+      id: 3,
+      name: "To Kill a Mockingbird".to_string(),
+      author_id: 3,
+    });
+
+    assert!(matches!(
+      db.check_foreign_keys(),
+      Err(Error::ForeignKeyViolation { table, column, row: 2 })
+        if table == "books" && column == "Author ID"
+    ));
+
+    assert!(matches!(
+      db.add_foreign_key("books", "Publisher ID", "authors", "ID"),
+      Err(Error::ColumnNotFound(_))
+    ));
+
+    assert!(matches!(
+      db.add_foreign_key("books", "Author ID", "publishers", "ID"),
+      Err(Error::TableNotFound(_))
+    ));
+
+    assert_eq!(db.foreign_keys().len(), 1);
+  }
+
+  #[test]
+  fn collect() {
+    let books = Table::from_rows(
+      "books",
+      (1..=4)
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: id % 2,
+        })
+        .collect(),
+    );
+
+    let mut odd = books
+      .into_iter()
+      .filter(|book| book.author_id == 1)
+      .collect::<Table<Book>>();
+
+    assert_eq!(odd.name, "");
+
+    odd.set_name("odd");
+
+    assert_eq!(odd.name, "odd");
+
+    assert_eq!(
+      odd.into_iter().map(|book| book.id).collect::<Vec<_>>(),
+      vec![1, 3]
+    );
+  }
+
+  #[test]
+  fn find_duplicates() {
+    let books = Table::from_rows(
+      "books",
+      [
+        (1, "1984"),
+        (2, "Animal Farm"),
+        (1, "Nineteen Eighty-Four"),
+        (3, ""),
+      ]
+      .into_iter()
+      .map(|(id, name)| Book {
+        id,
+        name: name.to_string(),
+        author_id: 1,
+      })
+      .collect(),
+    );
+
+    assert_eq!(
+      books
+        .find_duplicates(|book| book.id)
+        .rows
+        .iter()
+        .map(|book| book.name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["1984", "Nineteen Eighty-Four"]
+    );
+  }
+
+  #[test]
+  fn aggregate_all() {
+    #[derive(Debug, Clone, PartialEq)]
+    struct Summary {
+      total_books: usize,
+      distinct_authors: usize,
+    }
+
+    impl Row for Summary {
+      fn header() -> PrettyRow {
+        row!["Total Books", "Distinct Authors"]
+      }
+
+      fn to_pretty_row(&self) -> PrettyRow {
+        row![self.total_books, self.distinct_authors]
+      }
+    }
+
+    let books = Table::from_rows(
+      "books",
+      [1, 1, 2]
+        .into_iter()
+        .enumerate()
+        .map(|(id, author_id)| Book {
+          id: id as u32,
+          name: format!("Book {id}"),
+          author_id,
+        })
+        .collect(),
+    );
+
+    let summary = books.aggregate_all(|books| Summary {
+      total_books: books.len(),
+      distinct_authors: books
+        .iter()
+        .map(|book| book.author_id)
+        .collect::<HashSet<_>>()
+        .len(),
+    });
+
+    assert_eq!(
+      summary.rows,
+      vec![Summary {
+        total_books: 3,
+        distinct_authors: 2,
+      }]
+    );
+  }
+
+  #[test]
+  fn create_table_with_inserter() {
+    let mut db = Database::new();
+
+    let (books, mut insert) =
+      db.create_table_with_inserter::<Book>("books").unwrap();
+
+    insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    insert(Book {
+      id: 2,
+      name: "Animal Farm".to_string(),
+      author_id: 1,
+    });
+
+    assert_eq!(books.borrow().rows.len(), 2);
+
+    assert_eq!(db.from::<Book>("books").unwrap().borrow().rows.len(), 2);
+  }
+
+  #[test]
+  fn number_grouping() {
+    let mut books = Table::from_rows(
+      "books",
+      vec![Book {
+        id: 1000000,
+        name: "Animal Farm".to_string(),
+        author_id: 12,
+      }],
+    );
+
+    assert!(books.to_string().contains("1000000"));
+
+    books.set_number_grouping(true);
+
+    let rendered = books.to_string();
+
+    assert!(rendered.contains("1,000,000"));
+
+    assert!(rendered.contains("12 "));
+
+    assert_eq!(group_digits("-12345.678"), Some("-12,345.678".to_string()));
+
+    assert_eq!(group_digits("123"), Some("123".to_string()));
+
+    assert_eq!(group_digits("Animal Farm"), None);
+
+    assert_eq!(group_digits("1.2.3"), None);
+  }
+
+  #[test]
+  fn where_in() {
+    let books = Table::from_rows(
+      "books",
+      [(1, 1), (2, 2), (3, 1), (4, 3)]
+        .into_iter()
+        .map(|(id, author_id)| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id,
+        })
+        .collect(),
+    );
+
+    assert_eq!(
+      books
+        .where_in(|book| book.author_id, &[1])
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![1, 3]
+    );
+
+    assert!(books.where_in(|book| book.author_id, &[]).rows.is_empty());
+  }
+
+  #[test]
+  fn where_not_in() {
+    let books = Table::from_rows(
+      "books",
+      [(1, 1), (2, 2), (3, 1), (4, 3)]
+        .into_iter()
+        .map(|(id, author_id)| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id,
+        })
+        .collect(),
+    );
+
+    assert_eq!(
+      books
+        .where_not_in(|book| book.author_id, &[1, 3])
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![2]
+    );
+
+    assert_eq!(books.where_not_in(|book| book.author_id, &[]).rows.len(), 4);
+  }
+
+  #[test]
+  fn snapshot_table() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    let snapshot = db.snapshot_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 2,
+      name: "Animal Farm".to_string(),
+      author_id: 1,
+    });
+
+    assert_eq!(snapshot.rows.len(), 1);
+
+    assert_eq!(snapshot.rows[0].name, "1984");
+
+    assert_eq!(books.borrow().rows.len(), 2);
+
+    assert!(matches!(
+      db.snapshot_table::<Author>("books"),
+      Err(Error::InvalidRowType(_))
+    ));
+  }
+
+  fn parse_book(values: &[String]) -> Result<Book, Error> {
+    let number = |value: &String| {
+      value
+        .parse::<u32>()
+        .map_err(|error| Error::ParseError(error.to_string()))
+    };
+
+    Ok(Book {
+      id: number(&values[0])?,
+      name: values[1].clone(),
+      author_id: number(&values[2])?,
+    })
+  }
+
+  #[test]
+  fn load_csv() {
+    let mut books = Table::<Book>::new("books".to_string());
+
+    books
+      .load_csv(
+        "Book Number,Title,Writer\n1,1984,1\n2,\"Brave New World, Revisited\",2\n",
+        parse_book,
+      )
+      .unwrap();
+
+    assert_eq!(books.rows.len(), 2);
+
+    assert_eq!(books.rows[1].name, "Brave New World, Revisited");
+
+    let rendered = books.to_string();
+
+    assert!(rendered.contains("Book Number"));
+
+    assert!(rendered.contains("Writer"));
+
+    assert!(!rendered.contains("Author ID"));
+
+    assert!(matches!(
+      books.set_runtime_headers(vec!["ID".to_string()]),
+      Err(Error::ColumnCountMismatch {
+        expected: 3,
+        actual: 1
+      })
+    ));
+
+    let mut others = Table::<Book>::new("others".to_string());
+
+    assert!(matches!(
+      others.load_csv("ID,Name,Author ID\n1,1984,one\n", parse_book),
+      Err(Error::ParseError(_))
+    ));
+
+    assert!(others.rows.is_empty());
+
+    assert!(others.runtime_headers.is_none());
+  }
+
+  #[test]
+  fn group_map() {
+    let books = Table::from_rows(
+      "books",
+      [(1, 2), (2, 1), (3, 2), (4, 2)]
+        .into_iter()
+        .map(|(id, author_id)| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id,
+        })
+        .collect(),
+    );
+
+    assert_eq!(
+      books.group_map(
+        |book| book.author_id,
+        |author_id, books| format!(
+          "author {author_id} has {} books",
+          books.len()
+        )
+      ),
+      vec!["author 1 has 1 books", "author 2 has 3 books"]
+    );
+  }
+
+  #[test]
+  fn execute_insert() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    db.execute("INSERT INTO books VALUES (3, 'Brave New World', 2)")
+      .unwrap();
+
+    assert_eq!(
+      books.borrow().rows,
+      vec![Book {
+        id: 3,
+        name: "Brave New World".to_string(),
+        author_id: 2,
+      }]
+    );
+
+    assert!(db
+      .execute("SELECT * FROM books")
+      .unwrap()
+      .contains("Brave New World"));
+
+    assert!(matches!(
+      db.execute("INSERT INTO books VALUES (4, 'Island')"),
+      Err(Error::ColumnCountMismatch {
+        expected: 3,
+        actual: 2
+      })
+    ));
+
+    db.create_table::<Author>("authors").unwrap();
+
+    assert!(matches!(
+      db.execute("INSERT INTO authors VALUES (1, 'Aldous Huxley')"),
+      Err(Error::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn to_ascii() {
+    let books = Table::from_rows(
+      "books",
+      vec![Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      }],
+    );
+
+    let rendered = books.to_ascii();
+
+    assert!(rendered.is_ascii());
+
+    assert!(rendered.contains("| 1  | 1984 | 1         |"));
+
+    assert!(!books.to_string().is_ascii());
+  }
+
+  #[test]
+  fn with_qualified_headers() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    authors.borrow_mut().insert(Author {
+      id: 1,
+      name: "George Orwell".to_string(),
+    });
+
+    let joined_table = db
+      .cross_join(&books.borrow(), &authors.borrow())
+      .with_qualified_headers();
+
+    let qualified = vec![
+      "books.ID",
+      "books.Name",
+      "books.Author ID",
+      "authors.ID",
+      "authors.Name",
+    ];
+
+    assert_eq!(joined_table.headers(), qualified);
+
+    assert_eq!(joined_table.column_index("authors.ID"), Some(3));
+
+    assert!(joined_table.to_string().contains("authors.Name"));
+
+    let filtered = joined_table.filter(|_| true);
+
+    assert_eq!(filtered.headers(), qualified);
+
+    assert_eq!(
+      filtered
+        .sort_by_column_name("authors.Name", true)
+        .unwrap()
+        .headers(),
+      qualified
+    );
+  }
+
+  #[test]
+  fn top_percentile() {
+    let books = Table::from_rows(
+      "books",
+      [(1, 120), (2, 900), (3, 40), (4, 500)]
+        .into_iter()
+        .map(|(id, sales)| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: sales,
+        })
+        .collect(),
+    );
+
+    let sales = |book: &Book| f64::from(book.author_id);
+
+    let ids = |table: Table<Book>| {
+      table.rows.iter().map(|book| book.id).collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids(books.top_percentile(sales, 50.0)), vec![2, 4]);
+
+    assert_eq!(ids(books.top_percentile(sales, 10.0)), vec![2]);
+
+    assert_eq!(ids(books.top_percentile(sales, 250.0)), vec![1, 2, 3, 4]);
+
+    assert!(books.top_percentile(sales, -5.0).rows.is_empty());
+  }
+
+  #[test]
+  fn rename_table() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    db.create_table::<Author>("authors")
+      .unwrap()
+      .borrow_mut()
+      .insert(Author {
+        id: 1,
+        name: "George Orwell".to_string(),
+      });
+
+    db.add_foreign_key("books", "Author ID", "authors", "ID")
+      .unwrap();
+
+    db.rename_table("authors", "writers").unwrap();
+
+    assert_eq!(
+      db.foreign_keys(),
+      &[ForeignKey {
+        child_table: "books".to_string(),
+        child_column: "Author ID".to_string(),
+        parent_table: "writers".to_string(),
+        parent_column: "ID".to_string(),
+      }]
+    );
+
+    assert_eq!(
+      db.snapshot_table::<Author>("writers").unwrap().name,
+      "writers"
+    );
+
+    assert!(db.check_foreign_keys().is_ok());
+
+    assert!(matches!(
+      db.rename_table("authors", "others"),
+      Err(Error::TableNotFound(_))
+    ));
+
+    assert!(matches!(
+      db.rename_table("books", "writers"),
+      Err(Error::TableAlreadyExists(..))
+    ));
+
+    db.rename_table("books", "books").unwrap();
+
+    assert!(matches!(
+      db.rename_table("others", "others"),
+      Err(Error::TableNotFound(_))
+    ));
+
+    db.transform_table("writers", "authors", Author::clone, true)
+      .unwrap();
+
+    assert_eq!(db.foreign_keys()[0].parent_table, "authors");
+
+    assert!(db.check_foreign_keys().is_ok());
+  }
+
+  #[test]
+  fn or_default_row() {
+    let placeholder = Book {
+      id: 0,
+      name: "No data".to_string(),
+      author_id: 0,
+    };
+
+    let books = Table::from_rows(
+      "books",
+      vec![Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      }],
+    );
+
+    let empty = books
+      .filter(|book| book.author_id == 2)
+      .or_default_row(placeholder.clone());
+
+    assert_eq!(empty.rows, vec![placeholder.clone()]);
+
+    let untouched = books.clone().or_default_row(placeholder);
+
+    assert_eq!(untouched.rows, books.rows);
+  }
+
+  #[test]
+  fn interleave() {
+    let table = |ids: &[u32]| {
+      Table::from_rows(
+        "books",
+        ids
+          .iter()
+          .map(|&id| Book {
+            id,
+            name: format!("Book {id}"),
+            author_id: 1,
+          })
+          .collect(),
+      )
+    };
+
+    let before = table(&[1, 2, 3]);
+
+    let after = table(&[10, 20]);
+
+    assert_eq!(
+      before
+        .interleave(&after)
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![1, 10, 2, 20, 3]
+    );
+
+    assert_eq!(
+      after
+        .interleave(&before)
+        .rows
+        .iter()
+        .map(|book| book.id)
+        .collect::<Vec<_>>(),
+      vec![10, 1, 20, 2, 3]
+    );
+  }
+
+  #[test]
+  fn memory_estimate() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(
+      &(1..=100)
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: 1,
+        })
+        .collect::<Vec<_>>(),
+    );
 
-    let last_row = &joined_table.rows[3];
+    db.create_table::<Book>("drafts")
+      .unwrap()
+      .borrow_mut()
+      .insert(Book {
+        id: 1,
+        name: "Draft".to_string(),
+        author_id: 1,
+      });
 
-    assert_eq!(last_row.left.id, 2);
-    assert_eq!(last_row.left.name, "Animal Farm");
-    assert_eq!(last_row.left.author_id, 1);
-    assert_eq!(last_row.right.id, 2);
-    assert_eq!(last_row.right.name, "Aldous Huxley");
+    let estimate = db.memory_estimate();
+
+    assert_eq!(estimate.len(), 2);
+
+    assert!(estimate["books"] > estimate["drafts"]);
+
+    assert!(estimate["books"] >= 100 * std::mem::size_of::<Book>());
+  }
+
+  #[test]
+  fn load_csv_short_rows() {
+    let input = "ID,Name\n1,George Orwell\n2\n";
+
+    let parse = |values: &[String]| {
+      Ok(Author {
+        id: values[0].parse().unwrap(),
+        name: values[1].clone(),
+      })
+    };
+
+    let mut authors = Table::<Author>::new("authors".to_string());
+
+    assert!(matches!(
+      authors.load_csv(input, parse),
+      Err(Error::ColumnCountMismatch {
+        expected: 2,
+        actual: 1
+      })
+    ));
+
+    assert!(authors.rows.is_empty());
+
+    authors.set_pad_short_rows(true);
+
+    authors.load_csv(input, parse).unwrap();
+
+    assert_eq!(
+      authors.rows,
+      vec![
+        Author {
+          id: 1,
+          name: "George Orwell".to_string(),
+        },
+        Author {
+          id: 2,
+          name: String::new(),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn cross_join_limited() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(
+      &(1..=3)
+        .map(|id| Book {
+          id,
+          name: format!("Book {id}"),
+          author_id: 1,
+        })
+        .collect::<Vec<_>>(),
+    );
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    authors.borrow_mut().insert_many(
+      &(1..=3)
+        .map(|id| Author {
+          id,
+          name: format!("Author {id}"),
+        })
+        .collect::<Vec<_>>(),
+    );
+
+    let joined_table =
+      db.cross_join_limited(&books.borrow(), &authors.borrow(), 4);
+
+    assert_eq!(joined_table.rows.len(), 4);
+
+    assert_eq!(
+      joined_table
+        .rows
+        .iter()
+        .map(|row| (row.left.id, row.right.id))
+        .collect::<Vec<_>>(),
+      vec![(1, 1), (1, 2), (1, 3), (2, 1)]
+    );
+
+    assert_eq!(
+      db.cross_join_limited(&books.borrow(), &authors.borrow(), 100)
+        .rows
+        .len(),
+      9
+    );
+  }
+
+  #[test]
+  fn to_map() {
+    let books = Table::from_rows(
+      "books",
+      [(1, "1984"), (2, "Animal Farm"), (1, "Nineteen Eighty-Four")]
+        .into_iter()
+        .map(|(id, name)| Book {
+          id,
+          name: name.to_string(),
+          author_id: 1,
+        })
+        .collect(),
+    );
+
+    let map = books.to_map(|book| book.id);
+
+    assert_eq!(map.len(), 2);
+
+    assert_eq!(map[&2].name, "Animal Farm");
+
+    assert_eq!(map[&1].name, "Nineteen Eighty-Four");
+  }
+
+  #[test]
+  fn to_multi_map() {
+    let books = Table::from_rows(
+      "books",
+      [
+        (1, "1984", 1),
+        (2, "Brave New World", 2),
+        (3, "Animal Farm", 1),
+      ]
+      .into_iter()
+      .map(|(id, name, author_id)| Book {
+        id,
+        name: name.to_string(),
+        author_id,
+      })
+      .collect(),
+    );
+
+    let map = books.to_multi_map(|book| book.author_id);
+
+    assert_eq!(
+      map[&1]
+        .iter()
+        .map(|book| book.name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["1984", "Animal Farm"]
+    );
+
+    assert_eq!(map[&2].len(), 1);
+  }
+
+  #[test]
+  fn query() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 2,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    let (headers, records) =
+      db.query("SELECT * FROM books ORDER BY ID").unwrap();
+
+    assert_eq!(headers, vec!["ID", "Name", "Author ID"]);
+
+    assert_eq!(
+      records,
+      vec![vec!["1", "1984", "1"], vec!["2", "Animal Farm", "1"]]
+    );
+
+    assert_eq!(
+      db.query("SELECT COUNT(*) FROM books").unwrap(),
+      (vec!["COUNT(*)".to_string()], vec![vec!["2".to_string()]])
+    );
+
+    assert!(matches!(
+      db.query("INSERT INTO books VALUES (3, 'Island', 2)"),
+      Err(Error::Unsupported(_))
+    ));
+
+    assert_eq!(books.borrow().rows.len(), 2);
+  }
+
+  #[test]
+  fn order_by_cell_with() {
+    let authors = Table::from_rows(
+      "authors",
+      [(1, "Orwell"), (2, ""), (3, "Huxley"), (4, "Lee")]
+        .into_iter()
+        .map(|(id, name)| Author {
+          id,
+          name: name.to_string(),
+        })
+        .collect(),
+    );
+
+    let ids = |table: Table<Author>| {
+      table
+        .rows
+        .iter()
+        .map(|author| author.id)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+      ids(authors.order_by_cell_with(1, true, NullsOrder::First)),
+      vec![2, 3, 4, 1]
+    );
+
+    assert_eq!(
+      ids(authors.order_by_cell_with(1, true, NullsOrder::Last)),
+      vec![3, 4, 1, 2]
+    );
+
+    assert_eq!(
+      ids(authors.order_by_cell_with(1, false, NullsOrder::First)),
+      vec![2, 1, 4, 3]
+    );
+
+    assert_eq!(
+      ids(authors.order_by_cell_with(1, false, NullsOrder::Last)),
+      vec![1, 4, 3, 2]
+    );
+  }
+
+  #[test]
+  fn compact_clone() {
+    let mut books = Table::<Book>::new("books".to_string());
+
+    books.rows.reserve(64);
+
+    books.insert(Book {
+      id: 1,
+      name: "1984".to_string(),
+      author_id: 1,
+    });
+
+    let compact = books.compact_clone();
+
+    assert!(books.rows.capacity() > books.rows.len());
+
+    assert_eq!(compact.rows.capacity(), compact.rows.len());
+
+    assert_eq!(compact.rows, books.rows);
+
+    assert_eq!(compact.name, books.name);
+  }
+
+  #[test]
+  fn insert_if() {
+    let mut books = Table::<Book>::new("books".to_string());
+
+    let positive_id = |book: &Book| book.id > 0;
+
+    books
+      .insert_if(
+        Book {
+          id: 1,
+          name: "1984".to_string(),
+          author_id: 1,
+        },
+        positive_id,
+      )
+      .unwrap();
+
+    assert!(matches!(
+      books.insert_if(
+        Book {
+          id: 0,
+          name: "Untitled".to_string(),
+          author_id: 1,
+        },
+        positive_id,
+      ),
+      Err(Error::CheckFailed(table)) if table == "books"
+    ));
+
+    assert_eq!(books.rows.len(), 1);
+
+    assert_eq!(books.rows[0].id, 1);
+  }
+
+  #[test]
+  fn execute_group_by() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 10,
+      },
+      Book {
+        id: 2,
+        name: "Island".to_string(),
+        author_id: 2,
+      },
+      Book {
+        id: 3,
+        name: "Animal Farm".to_string(),
+        author_id: 10,
+      },
+    ]);
+
+    let (headers, records) = db
+      .query(r#"SELECT "Author ID", COUNT(*) FROM books GROUP BY "Author ID""#)
+      .unwrap();
+
+    assert_eq!(headers, vec!["Author ID", "COUNT(*)"]);
+
+    assert_eq!(records, vec![vec!["2", "1"], vec!["10", "2"]]);
+
+    assert!(matches!(
+      db.query("SELECT Title, COUNT(*) FROM books GROUP BY Title"),
+      Err(Error::ColumnNotFound(column)) if column == "Title"
+    ));
+
+    assert!(matches!(
+      db.query("SELECT ID, COUNT(*) FROM books GROUP BY ID ORDER BY ID"),
+      Err(Error::Unsupported(_))
+    ));
+  }
+
+  #[test]
+  fn estimate_cross_join() {
+    let mut db = Database::new();
+
+    let books = db.create_table::<Book>("books").unwrap();
+
+    let authors = db.create_table::<Author>("authors").unwrap();
+
+    books.borrow_mut().insert_many(&[
+      Book {
+        id: 1,
+        name: "1984".to_string(),
+        author_id: 1,
+      },
+      Book {
+        id: 2,
+        name: "Island".to_string(),
+        author_id: 2,
+      },
+      Book {
+        id: 3,
+        name: "Animal Farm".to_string(),
+        author_id: 1,
+      },
+    ]);
+
+    authors.borrow_mut().insert_many(&[
+      Author {
+        id: 1,
+        name: "George Orwell".to_string(),
+      },
+      Author {
+        id: 2,
+        name: "Aldous Huxley".to_string(),
+      },
+    ]);
+
+    let estimate = db.estimate_cross_join("books", "authors").unwrap();
+
+    assert_eq!(estimate, 6);
+
+    assert_eq!(
+      books.borrow().cross_size(authors.borrow().rows.len()),
+      estimate
+    );
+
+    assert_eq!(
+      db.cross_join(&books.borrow(), &authors.borrow()).rows.len(),
+      estimate
+    );
+
+    assert!(matches!(
+      db.estimate_cross_join("books", "publishers"),
+      Err(Error::TableNotFound(name)) if name == "publishers"
+    ));
+  }
+
+  #[test]
+  fn check_foreign_keys_with_short_rows() {
+    let mut db = Database::new();
+
+    let tags = db.create_table::<Tag>("tags").unwrap();
+
+    tags.borrow_mut().insert_many(&[
+      Tag {
+        name: "fiction".to_string(),
+        parent: None,
+      },
+      Tag {
+        name: "dystopia".to_string(),
+        parent: Some("fiction".to_string()),
+      },
+    ]);
+
+    db.add_foreign_key("tags", "Parent", "tags", "Name")
+      .unwrap();
+
+    assert!(db.check_foreign_keys().is_ok());
+
+    tags.borrow_mut().insert(Tag {
+      name: "satire".to_string(),
+      parent: Some("humour".to_string()),
+    });
+
+    assert!(matches!(
+      db.check_foreign_keys(),
+      Err(Error::ForeignKeyViolation { row: 2, .. })
+    ));
+  }
+
+  #[test]
+  fn derived_tables_keep_runtime_headers() {
+    let mut books = Table::<Book>::new("books".to_string())
+      .with_format(*format::consts::FORMAT_DEFAULT);
+
+    books.set_number_grouping(true);
+
+    books
+      .load_csv(
+        "Book Number,Title,Writer\n2,Island,1000\n1,1984,1\n",
+        parse_book,
+      )
+      .unwrap();
+
+    let sorted = books.sort_by_column_name("Title", true).unwrap();
+
+    assert_eq!(sorted.column_index("Title"), Some(1));
+
+    assert_eq!(sorted.rows[0].name, "1984");
+
+    let filtered = books.filter(|book| book.id > 1);
+
+    assert_eq!(filtered.column_index("Title"), Some(1));
+
+    assert_eq!(filtered.column_index("Name"), None);
+
+    assert_eq!(filtered.to_string(), filtered.to_ascii());
+
+    assert!(filtered.to_string().contains("1,000"));
+
+    for derived in [
+      books.order_by_cell(0, true),
+      books.merge_sorted(&books, |book| book.id),
+      books.interleave(&books),
+      books.top_percentile(|book| book.id as f64, 0.0),
+    ] {
+      assert_eq!(derived.headers(), books.headers());
+    }
+  }
+
+  #[test]
+  fn execute_group_by_with_short_rows() {
+    let mut db = Database::new();
+
+    let tags = db.create_table::<Tag>("tags").unwrap();
+
+    tags.borrow_mut().insert_many(&[
+      Tag {
+        name: "fiction".to_string(),
+        parent: None,
+      },
+      Tag {
+        name: "dystopia".to_string(),
+        parent: Some("fiction".to_string()),
+      },
+      Tag {
+        name: "poetry".to_string(),
+        parent: None,
+      },
+    ]);
+
+    let (_, records) = db
+      .query("SELECT Parent, COUNT(*) FROM tags GROUP BY Parent")
+      .unwrap();
+
+    assert_eq!(records, vec![vec!["", "2"], vec!["fiction", "1"]]);
+  }
+
+  #[test]
+  fn sort_by_column_name_with_short_rows() {
+    let tags = Table::from_rows(
+      "tags",
+      vec![
+        Tag {
+          name: "dystopia".to_string(),
+          parent: Some("fiction".to_string()),
+        },
+        Tag {
+          name: "fiction".to_string(),
+          parent: None,
+        },
+      ],
+    );
+
+    let sorted = tags.sort_by_column_name("Parent", true).unwrap();
+
+    assert_eq!(sorted.rows[0].name, "fiction");
+
+    assert_eq!(sorted.rows[1].name, "dystopia");
+  }
+
+  #[test]
+  fn select_columns_with_short_rows() {
+    let tags = Table::from_rows(
+      "tags",
+      vec![Tag {
+        name: "fiction".to_string(),
+        parent: None,
+      }],
+    );
+
+    assert_eq!(
+      tags.select_columns(&["Parent", "Name"]).unwrap().1,
+      vec![vec!["", "fiction"]]
+    );
+  }
+
+  #[test]
+  fn with_computed_column_with_short_rows() {
+    let tags = Table::from_rows(
+      "tags",
+      vec![Tag {
+        name: "fiction".to_string(),
+        parent: None,
+      }],
+    );
+
+    let (headers, records) =
+      tags.with_computed_column("Length", |tag| tag.name.len().to_string());
+
+    assert_eq!(headers, vec!["Name", "Parent", "Length"]);
+
+    assert_eq!(records, vec![vec!["fiction", "", "7"]]);
+  }
+
+  #[test]
+  fn table_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Table<Book>>();
+  }
+
+  #[test]
+  fn to_fixed_width_with_short_rows() {
+    let tags = Table::from_rows(
+      "tags",
+      vec![Tag {
+        name: "a".to_string(),
+        parent: None,
+      }],
+    );
+
+    assert_eq!(
+      tags.to_fixed_width(&[4, 4]).unwrap(),
+      "NamePare\na       \n"
+    );
+  }
+
+  #[test]
+  fn to_csv_with_short_rows() {
+    let tags = Table::from_rows(
+      "tags",
+      vec![
+        Tag {
+          name: "fiction".to_string(),
+          parent: None,
+        },
+        Tag {
+          name: "dystopia".to_string(),
+          parent: Some("fiction".to_string()),
+        },
+      ],
+    );
+
+    assert_eq!(
+      tags.to_csv().unwrap(),
+      "Name,Parent\nfiction,\ndystopia,fiction\n"
+    );
+  }
+
+  #[test]
+  fn transform_table_drops_stale_foreign_keys() {
+    let mut db = Database::new();
+
+    db.create_table::<Book>("books").unwrap();
+
+    db.create_table::<Author>("authors")
+      .unwrap()
+      .borrow_mut()
+      .insert(Author {
+        id: 1,
+        name: "George Orwell".to_string(),
+      });
+
+    db.add_foreign_key("books", "Author ID", "authors", "ID")
+      .unwrap();
+
+    let to_title = |author: &Author| Title {
+      name: author.name.clone(),
+    };
+
+    db.transform_table("authors", "names", to_title, true)
+      .unwrap();
+
+    assert!(db.foreign_keys().is_empty());
+
+    assert!(db.check_foreign_keys().is_ok());
   }
 }
@@ -0,0 +1,402 @@
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Identifier(String),
+  Literal(String),
+  Symbol(char),
+  Word(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Projection {
+  All,
+  Count,
+  GroupCount(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Statement {
+  Insert {
+    table: String,
+    values: Vec<String>,
+  },
+  Select {
+    projection: Projection,
+    table: String,
+    order_by: Option<(String, bool)>,
+  },
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, Error> {
+  let mut tokens = Vec::new();
+
+  let mut chars = sql.chars().peekable();
+
+  while let Some(&c) = chars.peek() {
+    match c {
+      c if c.is_whitespace() => {
+        chars.next();
+      }
+      '"' => {
+        chars.next();
+
+        let mut identifier = String::new();
+
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some(c) => identifier.push(c),
+            None => {
+              return Err(Error::ParseError(
+                "unterminated quoted identifier".to_string(),
+              ))
+            }
+          }
+        }
+
+        tokens.push(Token::Identifier(identifier));
+      }
+      '\'' => {
+        chars.next();
+
+        let mut literal = String::new();
+
+        loop {
+          match chars.next() {
+            Some('\'') if chars.peek() == Some(&'\'') => {
+              chars.next();
+              literal.push('\'');
+            }
+            Some('\'') => break,
+            Some(c) => literal.push(c),
+            None => {
+              return Err(Error::ParseError(
+                "unterminated string literal".to_string(),
+              ))
+            }
+          }
+        }
+
+        tokens.push(Token::Literal(literal));
+      }
+      '(' | ')' | ',' | '*' | ';' => {
+        chars.next();
+        tokens.push(Token::Symbol(c));
+      }
+      c if c.is_alphanumeric() || c == '_' || c == '-' => {
+        let mut word = String::new();
+
+        while let Some(&c) = chars.peek() {
+          if !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+            break;
+          }
+
+          word.push(c);
+          chars.next();
+        }
+
+        tokens.push(Token::Word(word));
+      }
+      c => {
+        return Err(Error::ParseError(format!("unexpected character '{c}'")))
+      }
+    }
+  }
+
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position)
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.position).cloned();
+    self.position += 1;
+    token
+  }
+
+  fn accept_keyword(&mut self, keyword: &str) -> bool {
+    match self.peek() {
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+        self.position += 1;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+    if self.accept_keyword(keyword) {
+      Ok(())
+    } else {
+      Err(Error::ParseError(format!("expected {keyword}")))
+    }
+  }
+
+  fn accept_symbol(&mut self, symbol: char) -> bool {
+    if self.peek() == Some(&Token::Symbol(symbol)) {
+      self.position += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect_symbol(&mut self, symbol: char) -> Result<(), Error> {
+    if self.accept_symbol(symbol) {
+      Ok(())
+    } else {
+      Err(Error::ParseError(format!("expected '{symbol}'")))
+    }
+  }
+
+  fn identifier(&mut self) -> Result<String, Error> {
+    match self.next() {
+      Some(Token::Word(word) | Token::Identifier(word)) => Ok(word),
+      _ => Err(Error::ParseError("expected an identifier".to_string())),
+    }
+  }
+
+  fn count(&mut self) -> Result<(), Error> {
+    self.expect_keyword("COUNT")?;
+    self.expect_symbol('(')?;
+    self.expect_symbol('*')?;
+    self.expect_symbol(')')
+  }
+
+  fn projection(&mut self) -> Result<Projection, Error> {
+    if self.accept_symbol('*') {
+      return Ok(Projection::All);
+    }
+
+    if matches!(
+      self.peek(),
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("COUNT")
+    ) {
+      self.count()?;
+      return Ok(Projection::Count);
+    }
+
+    let column = self.identifier()?;
+
+    self.expect_symbol(',')?;
+    self.count()?;
+
+    Ok(Projection::GroupCount(column))
+  }
+
+  fn select(&mut self) -> Result<Statement, Error> {
+    let projection = self.projection()?;
+
+    self.expect_keyword("FROM")?;
+
+    let table = self.identifier()?;
+
+    if let Projection::GroupCount(column) = &projection {
+      self.expect_keyword("GROUP")?;
+      self.expect_keyword("BY")?;
+
+      if self.identifier()? != *column {
+        return Err(Error::ParseError(format!(
+          "GROUP BY must name the selected column {column}"
+        )));
+      }
+    }
+
+    let order_by = if self.accept_keyword("ORDER") {
+      self.expect_keyword("BY")?;
+
+      let column = self.identifier()?;
+
+      let ascending = !self.accept_keyword("DESC");
+
+      if ascending {
+        self.accept_keyword("ASC");
+      }
+
+      Some((column, ascending))
+    } else {
+      None
+    };
+
+    Ok(Statement::Select {
+      projection,
+      table,
+      order_by,
+    })
+  }
+
+  fn value(&mut self) -> Result<String, Error> {
+    match self.next() {
+      Some(Token::Word(word)) if word.eq_ignore_ascii_case("NULL") => {
+        Ok(String::new())
+      }
+      Some(Token::Word(word) | Token::Literal(word)) => Ok(word),
+      _ => Err(Error::ParseError("expected a value".to_string())),
+    }
+  }
+
+  fn insert(&mut self) -> Result<Statement, Error> {
+    self.expect_keyword("INTO")?;
+
+    let table = self.identifier()?;
+
+    self.expect_keyword("VALUES")?;
+    self.expect_symbol('(')?;
+
+    let mut values = vec![self.value()?];
+
+    while self.accept_symbol(',') {
+      values.push(self.value()?);
+    }
+
+    self.expect_symbol(')')?;
+
+    Ok(Statement::Insert { table, values })
+  }
+
+  fn statement(&mut self) -> Result<Statement, Error> {
+    let statement = if self.accept_keyword("INSERT") {
+      self.insert()?
+    } else {
+      self.expect_keyword("SELECT")?;
+      self.select()?
+    };
+
+    self.accept_symbol(';');
+
+    match self.peek() {
+      Some(token) => Err(Error::ParseError(format!(
+        "unexpected trailing token {token:?}"
+      ))),
+      None => Ok(statement),
+    }
+  }
+}
+
+pub(crate) fn parse(sql: &str) -> Result<Statement, Error> {
+  Parser {
+    tokens: tokenize(sql)?,
+    position: 0,
+  }
+  .statement()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn select_all() {
+    assert_eq!(
+      parse("SELECT * FROM books").unwrap(),
+      Statement::Select {
+        projection: Projection::All,
+        table: "books".to_string(),
+        order_by: None,
+      }
+    );
+  }
+
+  #[test]
+  fn select_count() {
+    assert_eq!(
+      parse("select count(*) from books;").unwrap(),
+      Statement::Select {
+        projection: Projection::Count,
+        table: "books".to_string(),
+        order_by: None,
+      }
+    );
+  }
+
+  #[test]
+  fn group_by() {
+    assert_eq!(
+      parse(r#"SELECT "Author ID", COUNT(*) FROM books GROUP BY "Author ID""#)
+        .unwrap(),
+      Statement::Select {
+        projection: Projection::GroupCount("Author ID".to_string()),
+        table: "books".to_string(),
+        order_by: None,
+      }
+    );
+  }
+
+  #[test]
+  fn order_by() {
+    assert_eq!(
+      parse(r#"SELECT * FROM books ORDER BY "Author ID" DESC"#).unwrap(),
+      Statement::Select {
+        projection: Projection::All,
+        table: "books".to_string(),
+        order_by: Some(("Author ID".to_string(), false)),
+      }
+    );
+
+    assert_eq!(
+      parse("SELECT * FROM books ORDER BY ID ASC").unwrap(),
+      Statement::Select {
+        projection: Projection::All,
+        table: "books".to_string(),
+        order_by: Some(("ID".to_string(), true)),
+      }
+    );
+  }
+
+  #[test]
+  fn insert() {
+    assert_eq!(
+      parse("INSERT INTO books VALUES (3, 'Brave New World', 2)").unwrap(),
+      Statement::Insert {
+        table: "books".to_string(),
+        values: vec![
+          "3".to_string(),
+          "Brave New World".to_string(),
+          "2".to_string()
+        ],
+      }
+    );
+
+    assert_eq!(
+      parse("insert into scores values (-1.5, 'It''s', NULL);").unwrap(),
+      Statement::Insert {
+        table: "scores".to_string(),
+        values: vec!["-1.5".to_string(), "It's".to_string(), String::new()],
+      }
+    );
+  }
+
+  #[test]
+  fn invalid() {
+    for sql in [
+      "",
+      "DELETE FROM books",
+      "SELECT FROM books",
+      "SELECT * books",
+      "SELECT * FROM books ORDER ID",
+      "SELECT * FROM books LIMIT 1",
+      "SELECT ID FROM books GROUP BY ID",
+      "SELECT ID, COUNT(*) FROM books",
+      "SELECT ID, COUNT(*) FROM books GROUP BY Name",
+      "SELECT * FROM books GROUP BY ID",
+      "SELECT * FROM \"books",
+      "SELECT * FROM books?",
+      "INSERT books VALUES (1)",
+      "INSERT INTO books (1)",
+      "INSERT INTO books VALUES ()",
+      "INSERT INTO books VALUES (1, 'Island)",
+      "INSERT INTO books VALUES (1 2)",
+    ] {
+      assert!(matches!(parse(sql), Err(Error::ParseError(_))), "{sql}");
+    }
+  }
+}